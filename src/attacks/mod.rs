@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+mod modifiers;
+mod warning;
+
+use crate::lane::Lane;
+use crate::layout::{
+    SongPanel,
+    LayoutState,
+};
+use crate::judgement::{
+    metrics,
+    SongMetrics,
+};
+use crate::remote::{
+    not_spectating,
+    playing_locally,
+};
+use crate::coop::in_coop;
+use crate::song::SongState;
+use crate::team_markers::{
+    PlayerMarker,
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+};
+
+/// A perfect streak of this many notes launches an attack at the opponent, and so does every multiple of it
+const ATTACK_STREAK: u32 = 10;
+
+/// Something one player does to the other player's panel, for a little while
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Deserialize,Serialize)]
+#[derive(Reflect)]
+pub enum Attack {
+    /// Arrows in this lane are not drawn
+    HiddenLane(Lane),
+    /// Arrows hang back near the top, then rush down to the target line
+    SpeedBurst,
+    /// Arrows are drawn in the opposite lane, L1 <-> R2 and L2 <-> R1, and are hit in the lane they are drawn in
+    MirroredLanes,
+    /// Arrows fade out before they reach the target line
+    FadingArrows,
+}
+impl Attack {
+    /// Picks one of the attacks at random
+    fn random() -> Attack {
+        use rand::seq::SliceRandom;
+        let mut rng = rand::thread_rng();
+
+        let lane = *Lane::all()
+            .choose(&mut rng)
+            .expect("at least one lane");
+
+        *[
+            Attack::HiddenLane(lane),
+            Attack::SpeedBurst,
+            Attack::MirroredLanes,
+            Attack::FadingArrows,
+        ]
+            .choose(&mut rng)
+            .expect("at least one attack")
+    }
+    /// How long the attack lasts once it lands
+    pub fn duration_secs(self) -> f32 {
+        match self {
+            Attack::HiddenLane(_) => 6.0,
+            Attack::SpeedBurst => 4.0,
+            Attack::MirroredLanes => 6.0,
+            Attack::FadingArrows => 8.0,
+        }
+    }
+    /// Shown on the panel the attack lands on
+    pub fn warning_text(self) -> String {
+        match self {
+            Attack::HiddenLane(lane) => format!("{} hidden!", lane.as_str()),
+            Attack::SpeedBurst => "Speed burst!".to_string(),
+            Attack::MirroredLanes => "Mirrored!".to_string(),
+            Attack::FadingArrows => "Fade out!".to_string(),
+        }
+    }
+}
+
+/// Represents an attack launched by team `T`.
+/// Our attacks land on every remote user's panel, and remote users' attacks land on ours.
+#[derive(Event)]
+#[derive(Debug,Clone,Deserialize,Serialize)]
+pub struct RawAttackEvent<T: Marker> {
+    pub attack: Attack,
+    pub _team: T,
+}
+impl <T: Marker> RawAttackEvent<T> {
+    pub fn from(attack: Attack) -> RawAttackEvent<T> {
+        Self {
+            attack,
+            _team: T::marker(),
+        }
+    }
+}
+pub type AttackEvent = RawAttackEvent<PlayerMarker>;
+pub type RemoteAttackEvent = RawAttackEvent<EnemyMarker>;
+
+/// The attacks currently affecting a song panel. Lives on the `SongPanel` entity.
+#[derive(Component)]
+#[derive(Debug, Default)]
+pub struct ActiveAttacks {
+    attacks: Vec<(Attack, Timer)>,
+}
+impl ActiveAttacks {
+    /// Starts the attack, or starts it over if it was already going
+    fn add(&mut self, attack: Attack) {
+        let timer = Timer::from_seconds(attack.duration_secs(), TimerMode::Once);
+        match self.attacks.iter_mut().find(|(a, _)| *a == attack) {
+            Some((_, existing)) => *existing = timer,
+            None => self.attacks.push((attack, timer)),
+        }
+    }
+    fn tick(&mut self, delta: bevy::utils::Duration) {
+        self.attacks
+            .iter_mut()
+            .for_each(|(_, timer)| { timer.tick(delta); });
+        self.attacks
+            .retain(|(_, timer)| !timer.finished());
+    }
+    fn clear(&mut self) {
+        self.attacks.clear();
+    }
+    fn is_active(&self, attack: Attack) -> bool {
+        self.attacks.iter().any(|(a, _)| *a == attack)
+    }
+    pub fn is_lane_hidden(&self, lane: Lane) -> bool {
+        self.is_active(Attack::HiddenLane(lane))
+    }
+    pub fn is_speed_burst(&self) -> bool {
+        self.is_active(Attack::SpeedBurst)
+    }
+    pub fn is_mirrored(&self) -> bool {
+        self.is_active(Attack::MirroredLanes)
+    }
+    pub fn is_fading(&self) -> bool {
+        self.is_active(Attack::FadingArrows)
+    }
+    /// The lane an arrow from `lane` of the chart shows up in, and so the lane it has to be hit in
+    pub fn drawn_lane(&self, lane: Lane) -> Lane {
+        if self.is_mirrored() {
+            lane.mirrored()
+        } else {
+            lane
+        }
+    }
+}
+
+/// Launches an attack at the opponent every time the perfect streak passes a multiple of `ATTACK_STREAK`
+fn launch_attacks_on_streak(
+    metrics: Res<SongMetrics>,
+    mut last_streak: Local<u32>,
+    mut attack_ev: EventWriter<AttackEvent>,
+) {
+    let streak = metrics.streak();
+
+    if streak < *last_streak {
+        // the streak was broken since we last looked
+        *last_streak = 0;
+    }
+    // a chord can take the streak past a multiple without ever landing on it
+    let crossed = streak / ATTACK_STREAK - *last_streak / ATTACK_STREAK;
+    *last_streak = streak;
+
+    for _ in 0..crossed {
+        let attack = Attack::random();
+        log::info!("streak of {streak}, launching attack {attack:?}");
+        attack_ev.send(AttackEvent::from(attack));
+    }
+}
+
+fn setup_active_attacks<T: Marker>(
+    mut commands: Commands,
+    panel_q: Query<Entity, (With<SongPanel>, With<T>)>,
+) {
+    for panel in panel_q.iter() {
+        commands.entity(panel)
+                .insert(ActiveAttacks::default());
+    }
+}
+
+/// Attacks launched by team `A` land on team `T`'s panel
+fn land_attacks<T: Marker, A: Marker>(
+    mut attack_ev: EventReader<RawAttackEvent<A>>,
+    mut panel_q: Query<&mut ActiveAttacks, With<T>>,
+    song_state: Res<State<SongState<T>>>,
+) {
+    if !matches!(song_state.get(), SongState::Playing) {
+        // nothing to attack
+        attack_ev.clear();
+        return;
+    }
+    for ev in attack_ev.read() {
+        log::info!("attack {:?} landed on {} panel", ev.attack, T::as_str());
+        for mut active in panel_q.iter_mut() {
+            active.add(ev.attack);
+        }
+    }
+}
+
+fn tick_active_attacks(
+    time: Res<Time>,
+    mut panel_q: Query<&mut ActiveAttacks>,
+) {
+    for mut active in panel_q.iter_mut() {
+        active.tick(time.delta());
+    }
+}
+
+fn clear_active_attacks<T: Marker>(
+    mut panel_q: Query<&mut ActiveAttacks, With<T>>,
+) {
+    for mut active in panel_q.iter_mut() {
+        active.clear();
+    }
+}
+
+pub struct AttacksPlugin;
+impl Plugin for AttacksPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<AttackEvent>()
+            .add_event::<RemoteAttackEvent>()
+            .add_event::<RawAttackEvent<Enemy2Marker>>()
+            .add_event::<RawAttackEvent<Enemy3Marker>>()
+
+            .add_systems(Update, launch_attacks_on_streak
+                .after(metrics::update_metrics)
+                .run_if(not_spectating)
+                // partners do not attack each other
+                .run_if(not(in_coop))
+                // only the first keyboard player has a streak to launch from, so neither may attack
+                .run_if(not(playing_locally))
+            )
+            .add_systems(Update, tick_active_attacks)
+            .add_plugins(warning::AttackWarningPlugin)
+        ;
+
+        self
+            .build_for_panel::<PlayerMarker>(app)
+            .build_for_panel::<EnemyMarker>(app)
+            .build_for_panel::<Enemy2Marker>(app)
+            .build_for_panel::<Enemy3Marker>(app)
+
+            .build_for_team::<PlayerMarker, EnemyMarker>(app)
+            .build_for_team::<PlayerMarker, Enemy2Marker>(app)
+            .build_for_team::<PlayerMarker, Enemy3Marker>(app)
+            .build_for_team::<EnemyMarker, PlayerMarker>(app)
+            .build_for_team::<Enemy2Marker, PlayerMarker>(app)
+            .build_for_team::<Enemy3Marker, PlayerMarker>(app)
+        ;
+    }
+}
+impl AttacksPlugin {
+    /// Everything team `T`'s panel needs to be attacked
+    fn build_for_panel<T: Marker>(&self, app: &mut App) -> &Self {
+        app
+            .add_systems(OnEnter(LayoutState::Done), setup_active_attacks::<T>)
+            .add_systems(Update, modifiers::apply_attack_modifiers::<T>
+                .after(crate::song::position_arrows::<T>)
+                .run_if(in_state(SongState::Playing::<T>))
+            )
+            .add_systems(OnEnter(SongState::NotPlaying::<T>), clear_active_attacks::<T>)
+        ;
+        self
+    }
+    /// `T` is the team whose panel gets attacked, `A` is the team attacking it
+    fn build_for_team<T: Marker, A: Marker>(&self, app: &mut App) -> &Self {
+        app
+            .add_systems(Update, land_attacks::<T, A>
+                .before(modifiers::apply_attack_modifiers::<T>)
+            )
+        ;
+        self
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+
+use crate::layout::SongPanel;
+use crate::song::Arrow;
+use crate::team_markers::Marker;
+
+use super::ActiveAttacks;
+
+/// How far down the screen (from 0 at the top to 1 at the target line) fading arrows start to fade
+const FADE_START: f32 = 0.4;
+/// How far down the screen fading arrows are gone completely
+const FADE_END: f32 = 0.75;
+
+/// How sharply arrows rush down during a speed burst. 1 would be no burst at all
+const SPEED_BURST_POWER: f32 = 2.0;
+
+fn world() -> crate::layout::BBox {
+    crate::world()
+}
+
+/// Changes how the arrows are drawn according to the active attacks on the panel.
+/// Runs after the arrows are positioned. Only mirroring changes where they need to be hit, and judging follows it.
+pub fn apply_attack_modifiers<T: Marker>(
+    panel_q: Query<(&SongPanel, &ActiveAttacks), With<T>>,
+    mut arrow_q: Query<(&Arrow, &mut Transform, &mut Visibility, &Handle<ColorMaterial>), With<T>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let Ok((panel, active)) = panel_q.get_single() else {
+        return;
+    };
+
+    for (arrow, mut transform, mut visibility, material) in arrow_q.iter_mut() {
+
+        // put the arrow back in its lane, or in the opposite one
+        let lane = active.drawn_lane(arrow.lane());
+        transform.translation.x = panel.lane_bounds(lane).center().x;
+
+        // how far the arrow is towards the target line, from 0 at the top to 1 at the target line
+        let top = world().top();
+        let bottom = world().bottom();
+        let t = (top - transform.translation.y) / (top - bottom);
+
+        if active.is_speed_burst() && (0.0..1.0).contains(&t) {
+            // same start and finish, so the arrow still arrives on the beat
+            let t = t.powf(SPEED_BURST_POWER);
+            transform.translation.y = bottom * t + top * (1.0 - t);
+        }
+
+        // the hidden lane is a column of the panel, whichever arrows are drawn in it
+        let hidden = active.is_lane_hidden(lane);
+        let target_visibility = if hidden { Visibility::Hidden } else { Visibility::Inherited };
+        if *visibility != target_visibility {
+            *visibility = target_visibility;
+        }
+
+        let alpha = if active.is_fading() {
+            1.0 - ((t - FADE_START) / (FADE_END - FADE_START)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        // only touch the material when it changes, so we are not re-uploading it every frame
+        let needs_update = materials.get(material)
+            .is_some_and(|m| m.color.a() != alpha);
+        if needs_update {
+            if let Some(material) = materials.get_mut(material) {
+                material.color.set_a(alpha);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use bevy::text::{
+    Text2dBounds
+};
+
+use crate::layout::{
+    Layer,
+    SongPanel,
+    LayoutState,
+};
+use crate::team_markers::{
+    PlayerMarker,
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+};
+
+use super::RawAttackEvent;
+
+const WARNING_TEXT_COLOR: Color = Color::rgb(1.0, 0.75, 0.1); // amber
+const WARNING_FONT_SIZE: f32 = 60.0;
+/// How long the warning stays up after an attack lands
+const WARNING_DURATION: f32 = 1.5;
+/// How many times the warning blinks while it is up
+const WARNING_BLINKS: f32 = 3.0;
+
+/// Tells the player about an attack that just landed on their panel
+#[derive(Component)]
+pub struct AttackWarning {
+    shown_at: f32,
+}
+
+fn setup_attack_warning<T: Marker>(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    panel_q: Query<&SongPanel, With<T>>,
+) {
+    let Ok(panel) = panel_q.get_single() else {
+        return; // nobody is sitting in this seat
+    };
+
+    let font = asset_server.load(crate::BASE_FONT_NAME);
+    let style = TextStyle {
+        font,
+        font_size: WARNING_FONT_SIZE,
+        color: WARNING_TEXT_COLOR,
+    };
+    let text = Text {
+        sections: vec![
+            TextSection {
+                value: "".to_string(),
+                style,
+            }
+        ],
+        ..default()
+    };
+
+    // near the top of the panel, out of the way of the feedback text
+    let mut pos = panel.bounds().center();
+    pos.y = panel.bounds().top() - panel.bounds().height() * 0.15;
+    pos.z = Layer::TextAlerts.z();
+
+    commands.spawn((
+        Name::new(format!("attack-warning-{}", T::as_str())),
+        T::marker(),
+        AttackWarning {
+            shown_at: f32::NEG_INFINITY,
+        },
+        Text2dBundle {
+            text,
+            transform: Transform::from_translation(pos),
+            text_2d_bounds: Text2dBounds {
+                size: panel.bounds().size().truncate() // clips of the z component
+            },
+            ..default()
+        },
+    ));
+}
+
+/// Attacks launched by team `A` are shown on team `T`'s panel
+fn show_attack_warning<T: Marker, A: Marker>(
+    time: Res<Time>,
+    mut attack_ev: EventReader<RawAttackEvent<A>>,
+    mut warning_q: Query<(&mut Text, &mut AttackWarning), With<T>>,
+) {
+    let Some(ev) = attack_ev.read().last() else {
+        return; // nothing to do
+    };
+
+    let now = time.elapsed().as_secs_f32();
+    for (mut text, mut warning) in warning_q.iter_mut() {
+        warning.shown_at = now;
+        text.sections[0].value.clear();
+        text.sections[0].value.push_str(ev.attack.warning_text().as_str());
+    }
+}
+
+/// Blinks the warning, then clears it out
+fn animate_attack_warning(
+    time: Res<Time>,
+    mut warning_q: Query<(&mut Text, &AttackWarning)>,
+) {
+    let now = time.elapsed().as_secs_f32();
+
+    for (mut text, warning) in warning_q.iter_mut() {
+        let t = (now - warning.shown_at) / WARNING_DURATION;
+        if t >= 1.0 {
+            if !text.sections[0].value.is_empty() {
+                text.sections[0].value.clear();
+            }
+            continue;
+        }
+
+        let blink = (t * WARNING_BLINKS * std::f32::consts::TAU).cos() * 0.5 + 0.5;
+        text.sections[0].style.color.set_a(blink);
+    }
+}
+
+pub struct AttackWarningPlugin;
+impl Plugin for AttackWarningPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(LayoutState::Done), (
+                setup_attack_warning::<PlayerMarker>,
+                setup_attack_warning::<EnemyMarker>,
+                setup_attack_warning::<Enemy2Marker>,
+                setup_attack_warning::<Enemy3Marker>,
+            ))
+            .add_systems(Update, (
+                show_attack_warning::<PlayerMarker, EnemyMarker>,
+                show_attack_warning::<PlayerMarker, Enemy2Marker>,
+                show_attack_warning::<PlayerMarker, Enemy3Marker>,
+                show_attack_warning::<EnemyMarker, PlayerMarker>,
+                show_attack_warning::<Enemy2Marker, PlayerMarker>,
+                show_attack_warning::<Enemy3Marker, PlayerMarker>,
+            ))
+            .add_systems(Update, animate_attack_warning)
+        ;
+    }
+}
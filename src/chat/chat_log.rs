@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::team_markers::{
+    PlayerMarker,
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+    Team,
+};
+
+use super::{
+    ChatInput,
+    RawChatEvent,
+};
+
+const CHAT_TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const CHAT_FONT_SIZE: f32 = 24.0;
+/// Most lines of chat shown at once
+const CHAT_LOG_LINES: usize = 5;
+/// How long a line of chat stays up
+const CHAT_LINE_SECS: f32 = 15.0;
+
+#[derive(Debug, Clone)]
+struct ChatLine {
+    team: Team,
+    text: String,
+    received_at: f32,
+}
+
+/// The last few things said in chat, oldest first
+#[derive(Resource)]
+#[derive(Debug, Default)]
+struct ChatLog {
+    lines: VecDeque<ChatLine>,
+}
+
+#[derive(Component)]
+struct ChatLogText;
+
+fn speaker(team: Team) -> &'static str {
+    match team {
+        Team::Player => "You",
+        Team::Enemy => "Them",
+        Team::Enemy2 => "Them (2)",
+        Team::Enemy3 => "Them (3)",
+    }
+}
+
+fn setup_chat_log(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(crate::BASE_FONT_NAME);
+
+    commands.spawn((
+        ChatLogText,
+        TextBundle {
+            text: Text::from_section(
+                "".to_string(),
+                TextStyle {
+                    font,
+                    font_size: CHAT_FONT_SIZE,
+                    color: CHAT_TEXT_COLOR,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+fn record_chat<T: Marker>(
+    time: Res<Time>,
+    mut chat_log: ResMut<ChatLog>,
+    mut chat_ev: EventReader<RawChatEvent<T>>,
+) {
+    for ev in chat_ev.read() {
+        log::info!("{} said: {}", T::as_str(), ev.text);
+        chat_log.lines.push_back(ChatLine {
+            team: T::team(),
+            text: ev.text.clone(),
+            received_at: time.elapsed().as_secs_f32(),
+        });
+        if chat_log.lines.len() > CHAT_LOG_LINES {
+            chat_log.lines.pop_front();
+        }
+    }
+}
+
+/// Shows recent chat, and the message being typed
+fn update_chat_log_text(
+    time: Res<Time>,
+    chat_log: Res<ChatLog>,
+    chat_input: Res<ChatInput>,
+    mut text_q: Query<&mut Text, With<ChatLogText>>,
+) {
+    let now = time.elapsed().as_secs_f32();
+
+    let mut content = String::new();
+    chat_log.lines
+        .iter()
+        .filter(|line| now - line.received_at < CHAT_LINE_SECS)
+        .for_each(|line| {
+            content.push_str(format!("{}: {}\n", speaker(line.team), line.text).as_str());
+        });
+    if chat_input.is_typing() {
+        content.push_str(format!("Say: {}_", chat_input.draft()).as_str());
+    }
+
+    for mut text in text_q.iter_mut() {
+        if text.sections[0].value != content {
+            text.sections[0].value = content.clone();
+        }
+    }
+}
+
+pub struct ChatLogPlugin;
+impl Plugin for ChatLogPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ChatLog>()
+            .add_systems(Startup, setup_chat_log)
+            .add_systems(Update, (
+                record_chat::<PlayerMarker>,
+                record_chat::<EnemyMarker>,
+                record_chat::<Enemy2Marker>,
+                record_chat::<Enemy3Marker>,
+            ).before(update_chat_log_text))
+            .add_systems(Update, update_chat_log_text)
+        ;
+    }
+}
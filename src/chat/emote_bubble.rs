@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::layout::{
+    Layer,
+    SongPanel,
+};
+use crate::team_markers::{
+    PlayerMarker,
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+};
+
+use super::RawEmoteEvent;
+
+const EMOTE_TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const EMOTE_FONT_SIZE: f32 = 70.0;
+/// How long the emote floats before it is gone
+const EMOTE_DURATION: f32 = 2.0;
+/// How far the emote floats up, as a fraction of the panel height
+const EMOTE_RISE: f32 = 0.15;
+
+/// An emote floating up from the top of the sender's panel
+#[derive(Component)]
+pub struct EmoteBubble {
+    shown_at: f32,
+    start_y: f32,
+    rise: f32,
+}
+
+/// Emotes sent by team `T` float above their panel
+fn spawn_emote_bubbles<T: Marker>(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    panel_q: Query<&SongPanel, With<T>>,
+    bubble_q: Query<Entity, (With<EmoteBubble>, With<T>)>,
+    mut emote_ev: EventReader<RawEmoteEvent<T>>,
+) {
+    let Some(ev) = emote_ev.read().last() else {
+        return; // nothing to do
+    };
+    let Ok(panel) = panel_q.get_single() else {
+        return; // nobody is sitting in this seat
+    };
+
+    // a new emote replaces the one that was up
+    for bubble in bubble_q.iter() {
+        commands.entity(bubble).despawn();
+    }
+
+    let font = asset_server.load(crate::BASE_FONT_NAME);
+    let style = TextStyle {
+        font,
+        font_size: EMOTE_FONT_SIZE,
+        color: EMOTE_TEXT_COLOR,
+    };
+
+    let mut pos = panel.bounds().center();
+    pos.y = panel.bounds().top() - panel.bounds().height() * 0.3;
+    pos.z = Layer::TextAlerts.z();
+
+    commands.spawn((
+        Name::new(format!("emote-{}", T::as_str())),
+        T::marker(),
+        EmoteBubble {
+            shown_at: time.elapsed().as_secs_f32(),
+            start_y: pos.y,
+            rise: panel.bounds().height() * EMOTE_RISE,
+        },
+        Text2dBundle {
+            text: Text::from_section(ev.emote.text(), style),
+            transform: Transform::from_translation(pos),
+            ..default()
+        },
+    ));
+}
+
+/// Floats the emote up and fades it out, then gets rid of it
+fn animate_emote_bubbles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut bubble_q: Query<(Entity, &EmoteBubble, &mut Text, &mut Transform)>,
+) {
+    let now = time.elapsed().as_secs_f32();
+
+    for (entity, bubble, mut text, mut transform) in bubble_q.iter_mut() {
+        let t = (now - bubble.shown_at) / EMOTE_DURATION;
+        if t >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y = bubble.start_y + bubble.rise * t;
+        text.sections[0].style.color.set_a(1.0 - t * t);
+    }
+}
+
+pub struct EmoteBubblePlugin;
+impl Plugin for EmoteBubblePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                spawn_emote_bubbles::<PlayerMarker>,
+                spawn_emote_bubbles::<EnemyMarker>,
+                spawn_emote_bubbles::<Enemy2Marker>,
+                spawn_emote_bubbles::<Enemy3Marker>,
+            ))
+            .add_systems(Update, animate_emote_bubbles)
+        ;
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+use bevy::input::keyboard::KeyboardInput;
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+mod emote_bubble;
+mod chat_log;
+
+use crate::remote::{
+    not_spectating,
+    translate,
+};
+use crate::song::SongState;
+use crate::team_markers::{
+    PlayerMarker,
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+};
+
+/// Longest chat message we will send, or show from someone else
+pub const MAX_CHAT_LEN: usize = 120;
+
+/// Something short to say without typing. Sent with the number keys while not playing.
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Deserialize,Serialize)]
+pub enum Emote {
+    Hello,
+    GoodGame,
+    Wow,
+    Laugh,
+    BringIt,
+    Oops,
+}
+impl Emote {
+    pub const fn all() -> &'static [Emote] {
+        use Emote::*;
+        &[Hello, GoodGame, Wow, Laugh, BringIt, Oops]
+    }
+    pub fn text(self) -> &'static str {
+        use Emote::*;
+        match self {
+            Hello => "Hi!",
+            GoodGame => "GG",
+            Wow => "Wow!",
+            Laugh => "Haha",
+            BringIt => "Bring it!",
+            Oops => "Oops",
+        }
+    }
+    /// The key that sends this emote
+    fn keycode(self) -> KeyCode {
+        use Emote::*;
+        match self {
+            Hello => KeyCode::Digit1,
+            GoodGame => KeyCode::Digit2,
+            Wow => KeyCode::Digit3,
+            Laugh => KeyCode::Digit4,
+            BringIt => KeyCode::Digit5,
+            Oops => KeyCode::Digit6,
+        }
+    }
+}
+
+/// Team `T` said something in chat
+#[derive(Event)]
+#[derive(Debug,Clone)]
+pub struct RawChatEvent<T: Marker> {
+    pub text: String,
+    pub _team: T,
+}
+impl <T: Marker> RawChatEvent<T> {
+    /// Keeps the text to a single line of at most `MAX_CHAT_LEN` characters, no matter who sent it
+    pub fn from(text: &str) -> RawChatEvent<T> {
+        let text = text
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_CHAT_LEN)
+            .collect();
+        Self {
+            text,
+            _team: T::marker(),
+        }
+    }
+}
+pub type ChatEvent = RawChatEvent<PlayerMarker>;
+
+/// Team `T` sent an emote
+#[derive(Event)]
+#[derive(Debug,Clone)]
+pub struct RawEmoteEvent<T: Marker> {
+    pub emote: Emote,
+    pub _team: T,
+}
+impl <T: Marker> RawEmoteEvent<T> {
+    pub fn from(emote: Emote) -> RawEmoteEvent<T> {
+        Self {
+            emote,
+            _team: T::marker(),
+        }
+    }
+}
+pub type EmoteEvent = RawEmoteEvent<PlayerMarker>;
+
+/// Whether the local user is typing a chat message, and what they have typed so far
+#[derive(Resource)]
+#[derive(Debug, Default)]
+pub struct ChatInput {
+    typing: bool,
+    draft: String,
+}
+impl ChatInput {
+    pub fn is_typing(&self) -> bool {
+        self.typing
+    }
+    pub fn draft(&self) -> &str {
+        self.draft.as_str()
+    }
+    fn stop_typing(&mut self) {
+        self.typing = false;
+        self.draft.clear();
+    }
+}
+
+/// Run condition for keyboard shortcuts, which should not go off while typing a message
+pub fn not_typing(chat_input: Res<ChatInput>) -> bool {
+    !chat_input.is_typing()
+}
+
+/// Enter starts a message, then sends it. Escape throws it away.
+pub fn type_chat_message(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut keyboard_ev: EventReader<KeyboardInput>,
+    mut chat_input: ResMut<ChatInput>,
+    mut chat_ev: EventWriter<ChatEvent>,
+) {
+    use bevy::input::keyboard::Key;
+
+    if !chat_input.typing {
+        keyboard_ev.clear();
+        if keys.just_pressed(KeyCode::Enter) {
+            chat_input.typing = true;
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        chat_input.stop_typing();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        let draft = chat_input.draft.trim().to_string();
+        if !draft.is_empty() {
+            chat_ev.send(ChatEvent::from(draft.as_str()));
+        }
+        chat_input.stop_typing();
+        return;
+    }
+
+    for ev in keyboard_ev.read() {
+        if !ev.state.is_pressed() {
+            continue;
+        }
+        match &ev.logical_key {
+            Key::Backspace => {
+                chat_input.draft.pop();
+            }
+            Key::Space => {
+                chat_input.draft.push(' ');
+            }
+            Key::Character(c) if chat_input.draft.chars().count() + c.chars().count() <= MAX_CHAT_LEN => {
+                chat_input.draft.push_str(c.as_str());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send_emote_on_key(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut emote_ev: EventWriter<EmoteEvent>,
+) {
+    Emote::all()
+        .iter()
+        .filter(|emote| keys.just_pressed(emote.keycode()))
+        .for_each(|&emote| {
+            emote_ev.send(EmoteEvent::from(emote));
+        });
+}
+
+/// Songs start whether or not we are done typing, and we need the keys back to play
+fn stop_typing_on_song_start(
+    mut chat_input: ResMut<ChatInput>,
+) {
+    chat_input.stop_typing();
+}
+
+pub struct ChatPlugin;
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ChatInput>()
+            .add_event::<RawChatEvent<PlayerMarker>>()
+            .add_event::<RawChatEvent<EnemyMarker>>()
+            .add_event::<RawChatEvent<Enemy2Marker>>()
+            .add_event::<RawChatEvent<Enemy3Marker>>()
+            .add_event::<RawEmoteEvent<PlayerMarker>>()
+            .add_event::<RawEmoteEvent<EnemyMarker>>()
+            .add_event::<RawEmoteEvent<Enemy2Marker>>()
+            .add_event::<RawEmoteEvent<Enemy3Marker>>()
+
+            // only between songs, where the keys are not needed to play
+            .add_systems(Update, (
+                    type_chat_message,
+                    send_emote_on_key
+                        .after(type_chat_message)
+                        .run_if(not_typing),
+            )
+                .before(translate::translate_chat_from_local)
+                .run_if(in_state(SongState::NotPlaying::<PlayerMarker>))
+                .run_if(not_spectating)
+                // there is nobody to talk to, and the editor needs the keys
+                .run_if(not(crate::editor::is_editing))
+            )
+            .add_systems(OnEnter(SongState::SettingUp::<PlayerMarker>), stop_typing_on_song_start)
+            .add_plugins((
+                    emote_bubble::EmoteBubblePlugin,
+                    chat_log::ChatLogPlugin,
+            ))
+        ;
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::Result;
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::utils::Duration;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::CliArgs;
+use crate::input::RawLaneHit;
+use crate::judgement::{
+    grading::SuccessGrade,
+    CorrectHitEvent,
+    DroppedNoteEvent,
+};
+use crate::lane::Lane;
+use crate::remote::{
+    communicate::Comms,
+    not_spectating,
+    GameMessage,
+};
+use crate::song::{
+    Arrow,
+    ChartName,
+    LoadChartRequest,
+    SongState,
+};
+use crate::team_markers::PlayerMarker;
+
+/// How often we tell the partner which half we play, until we hear which half they play
+const ANNOUNCE_HALF_DURATION: Duration = Duration::from_secs(1);
+
+/// Which side of the shared panel a user plays in a co-op match
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaneHalf {
+    /// L1 and L2
+    Left,
+    /// R1 and R2
+    Right,
+}
+impl LaneHalf {
+    pub fn contains(self, lane: Lane) -> bool {
+        match self {
+            LaneHalf::Left => lane.is_left(),
+            LaneHalf::Right => !lane.is_left(),
+        }
+    }
+    fn other(self) -> LaneHalf {
+        match self {
+            LaneHalf::Left => LaneHalf::Right,
+            LaneHalf::Right => LaneHalf::Left,
+        }
+    }
+    fn as_str(self) -> &'static str {
+        match self {
+            LaneHalf::Left => "left",
+            LaneHalf::Right => "right",
+        }
+    }
+}
+
+pub fn parse_lane_half(source: &str) -> Result<LaneHalf> {
+    match source.trim().to_lowercase().as_str() {
+        "left" | "l" => Ok(LaneHalf::Left),
+        "right" | "r" => Ok(LaneHalf::Right),
+        other => anyhow::bail!("expected left or right, got {other:?}"),
+    }
+}
+
+/// Both users play one chart together on the player panel, each on their own half of the lanes.
+/// Each side judges only its own lanes and sends the results over, so both see the same combined score.
+/// Whoever plays the left half picks the charts.
+#[derive(Resource)]
+#[derive(Debug, Clone)]
+pub struct CoopMatch {
+    half: LaneHalf,
+    /// The half the partner says they play, once they tell us
+    partner_half: Option<LaneHalf>,
+    /// The partner's last pick, waiting for our song to end
+    pending_pick: Option<ChartName>,
+}
+impl CoopMatch {
+    /// Whether the local user plays this lane
+    pub fn owns(&self, lane: Lane) -> bool {
+        self.half.contains(lane)
+    }
+    /// Whether the partner plays the other half, so that every lane has someone on it
+    pub fn is_paired(&self) -> bool {
+        self.partner_half == Some(self.half.other())
+    }
+}
+
+/// Run condition for co-op matches
+pub fn in_coop(coop: Option<Res<CoopMatch>>) -> bool {
+    coop.is_some()
+}
+
+/// Run condition for the chart selector. In co-op, only the left half picks, once the partner has joined.
+pub fn picks_charts(coop: Option<Res<CoopMatch>>) -> bool {
+    coop.is_none_or(|coop| coop.is_paired() && coop.half == LaneHalf::Left)
+}
+
+fn knows_partner_half(coop: Res<CoopMatch>) -> bool {
+    coop.partner_half.is_some()
+}
+
+/// Messages from the partner that mean something different in co-op than from an opponent
+#[derive(Event)]
+#[derive(Debug, Clone)]
+pub enum PartnerEvent {
+    Half {
+        half: LaneHalf,
+        heard_partner: bool,
+    },
+    Pick(ChartName),
+    CorrectHit {
+        lane: Lane,
+        beat: f32,
+        grade: SuccessGrade,
+    },
+    DroppedNote {
+        lane: Lane,
+        arrival_beat: f32,
+    },
+}
+
+/// Turns the partner's messages into `PartnerEvent`s, instead of events for the enemy panel
+#[derive(SystemParam)]
+pub struct PartnerEventWriters<'w> {
+    coop: Option<Res<'w, CoopMatch>>,
+    partner: EventWriter<'w, PartnerEvent>,
+}
+impl PartnerEventWriters<'_> {
+    /// Takes the messages that are only about co-op, and gives back the rest
+    pub fn take(&mut self, msg: GameMessage) -> Option<GameMessage> {
+        if self.coop.is_none() {
+            return Some(msg);
+        }
+
+        use GameMessage::*;
+        let ev = match msg {
+            CoopHalf { half, heard_partner } => PartnerEvent::Half { half, heard_partner },
+            LoadChart { chart_name } => PartnerEvent::Pick(chart_name),
+            CorrectHit(ev) => PartnerEvent::CorrectHit {
+                lane: ev.lane_hit.lane(),
+                beat: ev.lane_hit.beat(),
+                grade: ev.grade,
+            },
+            DroppedNote { lane, arrival_beat } => PartnerEvent::DroppedNote { lane, arrival_beat },
+            // the partner judges their own hits and sends the results, and their copy of the chart is not shown
+            LaneHit { .. } | SyncSpawnerState(_) => return None,
+            msg => return Some(msg),
+        };
+        log::debug!("emitting partner event {ev:?}");
+        self.partner.send(ev);
+        None
+    }
+}
+
+fn start_coop_from_cli(
+    mut commands: Commands,
+    cli: Res<CliArgs>,
+) {
+    let Some(half) = cli.coop else {
+        return; // playing against each other
+    };
+    log::info!("starting a co-op match, playing the {} lanes", half.as_str());
+    commands.insert_resource(CoopMatch {
+        half,
+        partner_half: None,
+        pending_pick: None,
+    });
+}
+
+fn announce_half(
+    coop: Res<CoopMatch>,
+    mut comms: ResMut<Comms>,
+) {
+    comms.try_send_message(GameMessage::CoopHalf {
+        half: coop.half,
+        heard_partner: false,
+    });
+}
+
+/// Checks that the partner plays the other half, and answers them if they have not heard ours
+fn receive_partner_half(
+    mut coop: ResMut<CoopMatch>,
+    mut comms: ResMut<Comms>,
+    mut partner_ev: EventReader<PartnerEvent>,
+) {
+    for ev in partner_ev.read() {
+        let PartnerEvent::Half { half, heard_partner } = *ev else {
+            continue;
+        };
+        if coop.partner_half != Some(half) {
+            if half == coop.half {
+                log::error!(
+                    "partner is also playing the {} lanes, nobody is playing the {} lanes. One of you needs to restart with --coop {}",
+                    half.as_str(), half.other().as_str(), half.other().as_str(),
+                );
+            } else {
+                log::info!("partner is playing the {} lanes", half.as_str());
+            }
+            coop.partner_half = Some(half);
+        }
+        if !heard_partner {
+            comms.try_send_message(GameMessage::CoopHalf {
+                half: coop.half,
+                heard_partner: true,
+            });
+        }
+    }
+}
+
+/// The left half picks, the right half plays whatever they picked once its own song is over
+fn follow_partner_pick(
+    mut coop: ResMut<CoopMatch>,
+    song_state: Res<State<SongState<PlayerMarker>>>,
+    mut partner_ev: EventReader<PartnerEvent>,
+    mut load_chart_ev: EventWriter<LoadChartRequest<PlayerMarker>>,
+) {
+    for ev in partner_ev.read() {
+        let PartnerEvent::Pick(chart_name) = ev else {
+            continue;
+        };
+        if !coop.is_paired() || coop.half == LaneHalf::Left {
+            log::warn!("ignoring partner's pick of {chart_name}, only the left half picks");
+            continue;
+        }
+        coop.pending_pick = Some(chart_name.clone());
+    }
+
+    if !matches!(song_state.get(), SongState::NotPlaying) {
+        return; // still playing the last one
+    }
+    if let Some(chart_name) = coop.pending_pick.take() {
+        log::info!("following partner's pick of {chart_name}");
+        load_chart_ev.send(LoadChartRequest::from(chart_name));
+    }
+}
+
+/// The pending arrow in the lane closest to the beat
+fn nearest_pending<'a, I>(arrows: I, lane: Lane, beat: f32) -> Option<(Mut<'a, Arrow>, &'a Transform)>
+    where I: Iterator<Item = (Mut<'a, Arrow>, &'a Transform)>
+{
+    arrows
+        .filter(|(arrow, _)| arrow.lane() == lane && arrow.status().is_pending())
+        .min_by(|(a, _), (b, _)| {
+            (a.arrival_beat() - beat).abs().total_cmp(&(b.arrival_beat() - beat).abs())
+        })
+}
+
+/// The partner is the authority on their own lanes, so their results are taken as they are
+fn apply_partner_results(
+    coop: Res<CoopMatch>,
+    time: Res<Time>,
+    mut arrow_q: Query<(&mut Arrow, &Transform), With<PlayerMarker>>,
+    mut partner_ev: EventReader<PartnerEvent>,
+    mut correct_hit_ev: EventWriter<CorrectHitEvent>,
+    mut dropped_note_ev: EventWriter<DroppedNoteEvent>,
+) {
+    let now = time.elapsed().as_secs_f32();
+
+    for ev in partner_ev.read() {
+        let (lane, beat) = match *ev {
+            PartnerEvent::CorrectHit { lane, beat, .. } => (lane, beat),
+            PartnerEvent::DroppedNote { lane, arrival_beat } => (lane, arrival_beat),
+            _ => continue,
+        };
+        if !coop.is_paired() {
+            log::warn!("ignoring partner's result on {}, they are not playing the other half", lane.as_str());
+            continue;
+        }
+        if coop.owns(lane) {
+            log::warn!("ignoring partner's result on {}, which is not one of their lanes", lane.as_str());
+            continue;
+        }
+        let Some((mut arrow, transform)) = nearest_pending(arrow_q.iter_mut(), lane, beat) else {
+            log::debug!("no arrow on {} for the partner's result, it may have already gone by", lane.as_str());
+            continue;
+        };
+
+        match *ev {
+            PartnerEvent::CorrectHit { grade, .. } => {
+                arrow.mark_completed();
+                correct_hit_ev.send(CorrectHitEvent {
+                    lane_hit: RawLaneHit::from(lane, beat, now),
+                    arrow_pos: transform.translation,
+                    grade,
+                });
+            }
+            _ => {
+                dropped_note_ev.send(DroppedNoteEvent::new(arrow.clone()));
+                arrow.mark_dropped();
+            }
+        }
+    }
+}
+
+pub struct CoopPlugin;
+impl Plugin for CoopPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<PartnerEvent>()
+            .add_systems(Startup, start_coop_from_cli)
+            .add_systems(Update, (
+                    announce_half
+                        .run_if(not(knows_partner_half))
+                        .run_if(bevy::time::common_conditions::on_timer(ANNOUNCE_HALF_DURATION)),
+                    receive_partner_half,
+                    follow_partner_pick
+                        .after(receive_partner_half),
+                    apply_partner_results
+                        .after(receive_partner_half),
+            ).run_if(in_coop).run_if(not_spectating))
+        ;
+    }
+}
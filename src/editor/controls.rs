@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use bevy::input::mouse::{
+    MouseScrollUnit,
+    MouseWheel,
+};
+use bevy::window::PrimaryWindow;
+
+use crate::lane::Lane;
+use crate::layout::SongPanel;
+use crate::song::{
+    ChartAssets,
+    SongState,
+};
+use crate::team_markers::PlayerMarker;
+use crate::user_settings::UserSettings;
+
+use super::{
+    is_editing,
+    not_naming,
+    prompt::SaveAsPrompt,
+    save_into,
+    type_save_as_name,
+    view,
+    Editor,
+};
+
+/// Beats the cursor jumps with page up and page down
+const PAGE_BEATS: i64 = 4;
+
+/// Scrolling by pixels, e.g. on a touchpad, moves a row for about this many pixels
+const PIXELS_PER_ROW: f32 = 20.0;
+
+/// The forward and backward keys from recording move the cursor a row at a time, and the mouse wheel scrolls
+fn move_cursor(
+    settings: Res<UserSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut wheel_ev: EventReader<MouseWheel>,
+    mut scrolled: Local<f32>,
+    mut editor: ResMut<Editor>,
+) {
+    let keymap = &settings.keybindings.recording_keymap;
+    let page = PAGE_BEATS * editor.chart().rows_per_beat() as i64;
+
+    let mut rows = 0;
+    if keys.just_pressed(keymap.forward) {
+        rows += 1;
+    }
+    if keys.just_pressed(keymap.backward) {
+        rows -= 1;
+    }
+    if keys.just_pressed(KeyCode::PageDown) {
+        rows += page;
+    }
+    if keys.just_pressed(KeyCode::PageUp) {
+        rows -= page;
+    }
+
+    // scrolling down moves forward, the same as the forward key
+    for ev in wheel_ev.read() {
+        *scrolled -= match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / PIXELS_PER_ROW,
+        };
+    }
+    let whole_rows = scrolled.trunc();
+    *scrolled -= whole_rows;
+    rows += whole_rows as i64;
+
+    if rows != 0 {
+        editor.move_cursor(rows);
+    }
+}
+
+/// Left click adds or removes a note in the lane and row under the mouse, right click only removes
+fn edit_notes_with_mouse(
+    buttons: Res<ButtonInput<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    panel_q: Query<&SongPanel, With<PlayerMarker>>,
+    mut editor: ResMut<Editor>,
+) {
+    let add = buttons.just_pressed(MouseButton::Left);
+    let remove = buttons.just_pressed(MouseButton::Right);
+    if !add && !remove {
+        return;
+    }
+
+    let (Ok(window), Ok((camera, camera_transform)), Ok(panel)) = (window_q.get_single(), camera_q.get_single(), panel_q.get_single()) else {
+        return;
+    };
+    let Some(point) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return; // the mouse is outside the window
+    };
+
+    let Some(lane) = panel
+        .lanes()
+        .iter()
+        .find(|(_, bounds)| bounds.contains(point))
+        .map(|(lane, _)| lane)
+    else {
+        return; // not on the panel
+    };
+    let Some(row) = view::row_at_y(editor.as_ref(), point.y) else {
+        return;
+    };
+
+    if add {
+        editor.toggle_note(row, lane);
+    } else {
+        editor.remove_note(row, lane);
+    }
+}
+
+/// The lane keys toggle notes on the cursor row, and the usual shortcuts edit the chart
+fn handle_edit_shortcuts(
+    settings: Res<UserSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    mut prompt: ResMut<SaveAsPrompt>,
+    mut chart_assets: ResMut<ChartAssets>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if !ctrl {
+        let keymap = &settings.keybindings.lane_hit_keymap;
+        let row = editor.cursor_row();
+        for &lane in Lane::all().iter().filter(|&&lane| keys.just_pressed(keymap.keycode(lane))) {
+            editor.toggle_note(row, lane);
+        }
+
+        if keys.just_pressed(KeyCode::BracketLeft) {
+            editor.mark_selection_start();
+        }
+        if keys.just_pressed(KeyCode::BracketRight) {
+            editor.mark_selection_end();
+        }
+        if keys.just_pressed(KeyCode::Backslash) {
+            editor.clear_selection();
+        }
+        if keys.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+            editor.delete_selection();
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::KeyZ) {
+        if shift {
+            editor.redo();
+        } else {
+            editor.undo();
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyY) {
+        editor.redo();
+    }
+    if keys.just_pressed(KeyCode::KeyC) {
+        editor.copy_selection();
+    }
+    if keys.just_pressed(KeyCode::KeyX) {
+        editor.cut_selection();
+    }
+    if keys.just_pressed(KeyCode::KeyV) {
+        editor.paste_at_cursor();
+    }
+    if keys.just_pressed(KeyCode::KeyS) {
+        if shift {
+            prompt.open(editor.chart().chart_name());
+        } else {
+            save_into(editor.as_mut(), chart_assets.as_mut(), None);
+        }
+    }
+}
+
+pub struct EditorControlsPlugin;
+impl Plugin for EditorControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                    move_cursor,
+                    edit_notes_with_mouse
+                        .after(move_cursor),
+                    handle_edit_shortcuts
+                        .after(edit_notes_with_mouse),
+            )
+                // the key that opens the save as prompt should not end up in the name
+                .after(type_save_as_name)
+                .run_if(is_editing)
+                .run_if(not_naming)
+                // the keys are for playing while playtesting
+                .run_if(in_state(SongState::NotPlaying::<PlayerMarker>))
+            )
+        ;
+    }
+}
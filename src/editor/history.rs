@@ -0,0 +1,36 @@
+use crate::song::Note;
+
+/// Most edits we remember for undo
+const MAX_HISTORY: usize = 200;
+
+/// Every beat of a chart, with the notes on it
+pub type Beats = Vec<Vec<Note>>;
+
+/// Snapshots of the chart's notes from before each edit, so that edits can be undone and redone
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Beats>,
+    redo: Vec<Beats>,
+}
+impl History {
+    /// Remembers how the notes were before an edit. Anything undone before now can not be redone anymore.
+    pub fn record(&mut self, before: Beats) {
+        self.undo.push(before);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+    /// Swaps the current notes for how they were before the last edit
+    pub fn undo(&mut self, current: Beats) -> Option<Beats> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+    /// Swaps the current notes back to how they were before the last undo
+    pub fn redo(&mut self, current: Beats) -> Option<Beats> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        Some(next)
+    }
+}
@@ -0,0 +1,363 @@
+use std::ops::RangeInclusive;
+
+use anyhow::Result;
+use bevy::prelude::*;
+
+mod controls;
+mod history;
+mod playtest;
+mod prompt;
+mod view;
+mod waveform;
+
+use crate::{
+    CliArgs,
+    ConnectionMode,
+};
+use crate::lane::Lane;
+use crate::song::{
+    Chart,
+    ChartAssets,
+    ChartName,
+    Note,
+};
+
+use history::{
+    Beats,
+    History,
+};
+
+pub use prompt::{
+    not_naming,
+    type_save_as_name,
+};
+
+/// Chart names go in a file name, so we keep them to letters, numbers, `-` and `_`
+pub fn parse_chart_name(source: &str) -> Result<String> {
+    let name = source.trim();
+    if name.is_empty() {
+        anyhow::bail!("chart name can not be empty");
+    }
+    if let Some(c) = name.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+        anyhow::bail!("chart names may only use letters, numbers, '-' and '_', got {c:?}");
+    }
+    Ok(name.to_string())
+}
+
+/// What to edit, as given on the command line
+#[derive(clap::Args)]
+#[derive(Debug, Clone)]
+pub struct EditArgs {
+    /// The chart to edit, without the .json. If there is no chart by that name, a new one is started.
+    #[arg(value_parser=parse_chart_name)]
+    pub chart: String,
+
+    /// The song for a new chart, a file in the assets/sounds folder. Not needed to edit an existing chart.
+    #[arg(long, value_parser=crate::record::parse_sound_file)]
+    pub sound_file: Option<String>,
+
+    /// The tempo of a new chart, in beats per minute.
+    #[arg(long, default_value_t = 120.0, value_parser=crate::record::parse_bpm)]
+    pub bpm: f32,
+
+    /// How many chart rows to a beat in a new chart.
+    #[arg(long, default_value_t = 4)]
+    pub subdivisions: u32,
+}
+
+/// The chart being edited, and everything that goes along with editing it
+#[derive(Resource)]
+#[derive(Debug)]
+pub struct Editor {
+    /// The chart as it was last loaded or saved. Its notes are replaced by `beats` when saving
+    chart: Chart,
+    /// The notes being edited, one list of notes per row
+    beats: Beats,
+    /// The row sitting on the target line
+    cursor_row: usize,
+    /// Either end of the selected rows, in the order they were marked
+    selection: (Option<usize>, Option<usize>),
+    /// Rows copied with the copy or cut shortcut
+    clipboard: Beats,
+    history: History,
+    /// Set when there are edits that are not saved yet
+    dirty: bool,
+    /// Set when escape was pressed with unsaved edits, so that pressing it again quits anyway
+    quit_warned: bool,
+    /// The last thing that happened, shown in the status line
+    status: String,
+}
+impl Editor {
+    fn new(chart: Chart) -> Editor {
+        let beats = chart.beats().to_vec();
+        Editor {
+            chart,
+            beats,
+            cursor_row: 0,
+            selection: (None, None),
+            clipboard: Vec::new(),
+            history: History::default(),
+            dirty: false,
+            quit_warned: false,
+            status: String::new(),
+        }
+    }
+    pub fn chart(&self) -> &Chart {
+        &self.chart
+    }
+    pub fn cursor_row(&self) -> usize {
+        self.cursor_row
+    }
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+    /// Whether quitting is fine, because everything is saved or the user was already warned it is not
+    pub fn confirm_quit(&mut self) -> bool {
+        if !self.dirty || self.quit_warned {
+            return true;
+        }
+        self.quit_warned = true;
+        self.set_status("there are unsaved edits, save with ctrl+s or press escape again to quit without saving");
+        false
+    }
+    pub fn status(&self) -> &str {
+        self.status.as_str()
+    }
+    fn set_status(&mut self, status: impl Into<String>) {
+        self.status = status.into();
+    }
+    /// Every note being edited, along with the row it is on
+    pub fn notes(&self) -> impl Iterator<Item = (usize, Lane)> + '_ {
+        self.beats
+            .iter()
+            .enumerate()
+            .flat_map(|(row, notes)| notes.iter().map(move |note| (row, note.lane())))
+    }
+    /// The selected rows, once both ends are marked
+    pub fn selection(&self) -> Option<RangeInclusive<usize>> {
+        let (Some(a), Some(b)) = self.selection else {
+            return None;
+        };
+        Some(a.min(b)..=a.max(b))
+    }
+
+    pub fn move_cursor(&mut self, rows: i64) {
+        self.cursor_row = self.cursor_row.saturating_add_signed(rows as isize);
+    }
+
+    /// Makes an undoable change to the notes
+    fn edit(&mut self, change: impl FnOnce(&mut Beats)) {
+        self.history.record(self.beats.clone());
+        change(&mut self.beats);
+        self.dirty = true;
+        self.quit_warned = false;
+    }
+    pub fn has_note(&self, row: usize, lane: Lane) -> bool {
+        self.beats
+            .get(row)
+            .is_some_and(|notes| notes.iter().any(|note| note.lane() == lane))
+    }
+    pub fn toggle_note(&mut self, row: usize, lane: Lane) {
+        if self.has_note(row, lane) {
+            self.remove_note(row, lane);
+        } else {
+            self.edit(|beats| {
+                if beats.len() <= row {
+                    beats.resize(row + 1, Vec::new());
+                }
+                beats[row].push(Note::new(lane));
+            });
+        }
+    }
+    pub fn remove_note(&mut self, row: usize, lane: Lane) {
+        if !self.has_note(row, lane) {
+            return;
+        }
+        self.edit(|beats| beats[row].retain(|note| note.lane() != lane));
+    }
+
+    pub fn undo(&mut self) {
+        match self.history.undo(self.beats.clone()) {
+            Some(beats) => {
+                self.beats = beats;
+                self.dirty = true;
+                self.quit_warned = false;
+                self.set_status("undid the last edit");
+            }
+            None => self.set_status("nothing to undo"),
+        }
+    }
+    pub fn redo(&mut self) {
+        match self.history.redo(self.beats.clone()) {
+            Some(beats) => {
+                self.beats = beats;
+                self.dirty = true;
+                self.quit_warned = false;
+                self.set_status("redid the last edit");
+            }
+            None => self.set_status("nothing to redo"),
+        }
+    }
+
+    /// Marks one end of the selection at the cursor
+    pub fn mark_selection_start(&mut self) {
+        self.selection.0 = Some(self.cursor_row);
+    }
+    /// Marks the other end of the selection at the cursor
+    pub fn mark_selection_end(&mut self) {
+        self.selection.1 = Some(self.cursor_row);
+    }
+    pub fn clear_selection(&mut self) {
+        self.selection = (None, None);
+    }
+    /// The notes on the selected rows, with empty rows for any past the end of the chart
+    fn selected_beats(&self, rows: &RangeInclusive<usize>) -> Beats {
+        rows.clone()
+            .map(|row| self.beats.get(row).cloned().unwrap_or_default())
+            .collect()
+    }
+    pub fn copy_selection(&mut self) {
+        let Some(rows) = self.selection() else {
+            self.set_status("mark a selection with [ and ] first");
+            return;
+        };
+        self.clipboard = self.selected_beats(&rows);
+        self.set_status(format!("copied {} rows", self.clipboard.len()));
+    }
+    pub fn cut_selection(&mut self) {
+        let Some(rows) = self.selection() else {
+            self.set_status("mark a selection with [ and ] first");
+            return;
+        };
+        self.clipboard = self.selected_beats(&rows);
+        self.clear_rows(rows);
+        self.set_status(format!("cut {} rows", self.clipboard.len()));
+    }
+    pub fn delete_selection(&mut self) {
+        let Some(rows) = self.selection() else {
+            return;
+        };
+        self.set_status(format!("cleared {} rows", rows.end() - rows.start() + 1));
+        self.clear_rows(rows);
+    }
+    fn clear_rows(&mut self, rows: RangeInclusive<usize>) {
+        self.edit(|beats| {
+            beats
+                .iter_mut()
+                .skip(*rows.start())
+                .take(rows.end() - rows.start() + 1)
+                .for_each(|notes| notes.clear());
+        });
+    }
+    /// Copied rows replace whatever was on the rows from the cursor onwards
+    pub fn paste_at_cursor(&mut self) {
+        if self.clipboard.is_empty() {
+            self.set_status("nothing to paste");
+            return;
+        }
+        let start = self.cursor_row;
+        let pasted = self.clipboard.clone();
+        let count = pasted.len();
+        self.edit(|beats| {
+            if beats.len() < start + count {
+                beats.resize(start + count, Vec::new());
+            }
+            beats[start..start + count].clone_from_slice(pasted.as_slice());
+        });
+        self.set_status(format!("pasted {count} rows at row {start}"));
+    }
+
+    /// The chart with the edited notes, leaving off empty rows at the end
+    fn edited_chart(&self) -> Chart {
+        let mut beats = self.beats.clone();
+        while beats.last().is_some_and(|notes| notes.is_empty()) {
+            beats.pop();
+        }
+        self.chart.with_beats(beats)
+    }
+    /// Writes the chart over the one it was loaded from
+    fn save(&mut self) -> Result<Chart> {
+        let chart = self.edited_chart();
+        let path = chart.save()?;
+        self.set_status(format!("saved to {}", path.display()));
+        self.chart = chart.clone();
+        self.dirty = false;
+        Ok(chart)
+    }
+    /// Writes the chart under a new name, and keeps on editing that one
+    fn save_as(&mut self, name: ChartName) -> Result<Chart> {
+        let chart = self.edited_chart().renamed(name);
+        let path = chart.save_new()?;
+        self.set_status(format!("saved as {}", path.display()));
+        self.chart = chart.clone();
+        self.dirty = false;
+        Ok(chart)
+    }
+}
+
+/// Saves the chart, and makes the saved chart available to play right away
+fn save_into(editor: &mut Editor, chart_assets: &mut ChartAssets, name: Option<ChartName>) {
+    let saved = match name {
+        Some(name) => editor.save_as(name),
+        None => editor.save(),
+    };
+    match saved {
+        Ok(chart) => chart_assets.insert(chart),
+        Err(e) => {
+            log::error!("unable to save chart: {e:?}");
+            editor.set_status(format!("unable to save: {e}"));
+        }
+    }
+}
+
+fn start_editor(
+    mut commands: Commands,
+    cli: Res<CliArgs>,
+    chart_assets: Res<ChartAssets>,
+) {
+    let ConnectionMode::Edit { args } = &cli.mode else {
+        return;
+    };
+
+    let name = ChartName::new(args.chart.as_str());
+    let chart = match chart_assets.try_get(&name) {
+        Some(chart) => {
+            log::info!("editing chart {name}");
+            chart.as_ref().clone()
+        }
+        None => {
+            log::info!("starting new chart {name} at {} bpm", args.bpm);
+            let rows_per_beat = args.subdivisions.max(1);
+            Chart::new(
+                name,
+                None,
+                60.0 / args.bpm / rows_per_beat as f32,
+                crate::record::LEAD_TIME_BEATS * rows_per_beat as f32,
+                args.sound_file.clone(),
+                Vec::new(),
+            ).with_rows_per_beat(rows_per_beat)
+        }
+    };
+    commands.insert_resource(Editor::new(chart));
+}
+
+/// Run condition for the editor, and for everything that should stay out of its way
+pub fn is_editing(editor: Option<Res<Editor>>) -> bool {
+    editor.is_some()
+}
+
+pub struct EditorPlugin;
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, start_editor)
+            .add_plugins((
+                    controls::EditorControlsPlugin,
+                    playtest::PlaytestPlugin,
+                    prompt::SaveAsPromptPlugin,
+                    view::EditorViewPlugin,
+                    waveform::WaveformPlugin,
+            ))
+        ;
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::song::{
+    ArrowSpawner,
+    SeekedAudio,
+    SongState,
+};
+use crate::team_markers::{
+    Marker,
+    PlayerMarker,
+};
+use crate::user_settings::UserSettings;
+
+use super::{
+    is_editing,
+    not_naming,
+    Editor,
+};
+
+/// The song to play along with the chart, and where the playtest started from
+#[derive(Resource)]
+#[derive(Debug, Default)]
+pub struct Playtest {
+    song: Option<Handle<AudioSource>>,
+    /// The cursor row when the playtest started, while it is going
+    from_row: Option<usize>,
+}
+impl Playtest {
+    pub fn is_playing(&self) -> bool {
+        self.from_row.is_some()
+    }
+}
+
+/// Loads the song up front, so that it is ready to seek into by the time the playtest starts
+fn load_playtest_song(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    asset_server: Res<AssetServer>,
+) {
+    let song = editor
+        .chart()
+        .sound_file()
+        .map(|filename| asset_server.load(format!("sounds/{filename}")));
+    commands.insert_resource(Playtest {
+        song,
+        from_row: None,
+    });
+}
+
+/// Run condition for the key that starts and stops the playtest
+fn playtest_key_pressed(
+    settings: Res<UserSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+) -> bool {
+    keys.just_pressed(settings.keybindings.recording_keymap.playtest)
+}
+
+/// Plays the chart as it is being edited, starting from the cursor.
+/// The cursor row comes in at the top of the screen, with the song seeked to match.
+fn start_playtest(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut editor: ResMut<Editor>,
+    mut playtest: ResMut<Playtest>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut seeked_audio: ResMut<Assets<SeekedAudio>>,
+    mut state: ResMut<NextState<SongState<PlayerMarker>>>,
+) {
+    let row = editor.cursor_row();
+    let chart = Arc::new(editor.edited_chart());
+    let skip = Duration::from_secs_f32(row as f32 * chart.beat_duration_secs());
+
+    let song = playtest.song
+        .as_ref()
+        .and_then(|handle| audio_sources.get(handle));
+    if playtest.song.is_some() && song.is_none() {
+        log::warn!("song is not loaded yet, playtesting without it");
+    }
+    let source = song
+        .map(|song| seeked_audio.add(SeekedAudio::new(song.clone(), skip)))
+        .unwrap_or_default();
+
+    log::info!("playtesting {} from row {row}", chart.chart_name());
+    let spawner = ArrowSpawner::<PlayerMarker>::create_from_beat(chart, &time, row as f32);
+    commands.spawn((
+        Name::new(format!("spawner-{}", PlayerMarker::as_str())),
+        spawner,
+        AudioSourceBundle {
+            source,
+            ..default()
+        },
+        PlayerMarker,
+    ));
+    state.set(SongState::SettingUp);
+
+    playtest.from_row = Some(row);
+    editor.set_status(format!("playtesting from row {row}"));
+}
+
+fn stop_playtest(
+    mut state: ResMut<NextState<SongState<PlayerMarker>>>,
+) {
+    state.set(SongState::NotPlaying);
+}
+
+/// Whether the song ended or was stopped, the editor comes back where it left off
+fn finish_playtest(
+    mut editor: ResMut<Editor>,
+    mut playtest: ResMut<Playtest>,
+) {
+    let Some(row) = playtest.from_row.take() else {
+        return;
+    };
+    editor.cursor_row = row;
+    editor.set_status("stopped playtesting");
+}
+
+pub struct PlaytestPlugin;
+impl Plugin for PlaytestPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, load_playtest_song
+                .after(super::start_editor)
+                .run_if(is_editing)
+            )
+            .add_systems(Update, start_playtest
+                .run_if(is_editing)
+                .run_if(not_naming)
+                .run_if(in_state(SongState::NotPlaying::<PlayerMarker>))
+                .run_if(playtest_key_pressed)
+            )
+            .add_systems(Update, stop_playtest
+                .run_if(is_editing)
+                .run_if(in_state(SongState::Playing::<PlayerMarker>))
+                .run_if(playtest_key_pressed)
+            )
+            .add_systems(OnEnter(SongState::NotPlaying::<PlayerMarker>), finish_playtest.run_if(is_editing))
+        ;
+    }
+}
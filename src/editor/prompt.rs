@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy::input::keyboard::KeyboardInput;
+
+use crate::song::{
+    ChartAssets,
+    ChartName,
+};
+
+use super::{
+    is_editing,
+    parse_chart_name,
+    save_into,
+    Editor,
+};
+
+/// Longest chart name we let the user type
+const MAX_NAME_LEN: usize = 64;
+
+/// Whether the user is typing a name to save the chart as, and what they have typed so far
+#[derive(Resource)]
+#[derive(Debug, Default)]
+pub struct SaveAsPrompt {
+    naming: bool,
+    draft: String,
+}
+impl SaveAsPrompt {
+    pub fn is_naming(&self) -> bool {
+        self.naming
+    }
+    pub fn draft(&self) -> &str {
+        self.draft.as_str()
+    }
+    /// Starts typing a new name, beginning from the current one
+    pub fn open(&mut self, current: &ChartName) {
+        self.naming = true;
+        self.draft = current.to_string();
+    }
+    fn close(&mut self) {
+        self.naming = false;
+        self.draft.clear();
+    }
+}
+
+/// Run condition for the editor's shortcuts, which should not go off while typing a name
+pub fn not_naming(prompt: Res<SaveAsPrompt>) -> bool {
+    !prompt.is_naming()
+}
+
+/// Enter saves under the typed name. Escape goes back to editing without saving.
+pub fn type_save_as_name(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut keyboard_ev: EventReader<KeyboardInput>,
+    mut prompt: ResMut<SaveAsPrompt>,
+    mut editor: ResMut<Editor>,
+    mut chart_assets: ResMut<ChartAssets>,
+) {
+    use bevy::input::keyboard::Key;
+
+    if !prompt.naming {
+        keyboard_ev.clear();
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        prompt.close();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        match parse_chart_name(prompt.draft()) {
+            Ok(name) => {
+                save_into(editor.as_mut(), chart_assets.as_mut(), Some(ChartName::new(name)));
+                prompt.close();
+            }
+            // keep the prompt open, so the name can be fixed
+            Err(e) => editor.set_status(e.to_string()),
+        }
+        return;
+    }
+
+    for ev in keyboard_ev.read() {
+        if !ev.state.is_pressed() {
+            continue;
+        }
+        match &ev.logical_key {
+            Key::Backspace => {
+                prompt.draft.pop();
+            }
+            Key::Character(c) if prompt.draft.chars().count() + c.chars().count() <= MAX_NAME_LEN => {
+                prompt.draft.push_str(c.as_str());
+            }
+            _ => {}
+        }
+    }
+}
+
+pub struct SaveAsPromptPlugin;
+impl Plugin for SaveAsPromptPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SaveAsPrompt>()
+            .add_systems(Update, type_save_as_name.run_if(is_editing))
+        ;
+    }
+}
@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+
+use crate::layout::{
+    BBox,
+    Layer,
+    LayoutState,
+    SongPanel,
+};
+use crate::song::{
+    Arrow,
+    SongState,
+};
+use crate::team_markers::PlayerMarker;
+
+use super::{
+    is_editing,
+    playtest::Playtest,
+    prompt::SaveAsPrompt,
+    Editor,
+};
+
+const STATUS_TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const STATUS_FONT_SIZE: f32 = 22.0;
+
+/// Grid lines on the first row of a measure, on the first row of every other beat, and on every other row
+const MEASURE_LINE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.5);
+const BEAT_LINE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+const ROW_LINE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.08);
+const GRID_LINE_HEIGHT: f32 = 2.0;
+const CURSOR_LINE_COLOR: Color = Color::rgba(1.0, 0.75, 0.1, 0.8); // amber
+const CURSOR_LINE_HEIGHT: f32 = 4.0;
+const SELECTION_COLOR: Color = Color::rgba(0.3, 0.5, 1.0, 0.2);
+
+/// Beats to a measure, for the grid lines
+const BEATS_PER_MEASURE: u32 = 4;
+
+const HELP_TEXT: &str = "click: add/remove note   right click: remove   lane keys: toggle at cursor   up/down/wheel: scroll\n\
+[ ]: select   \\: unselect   del: clear   ctrl+c/x/v: copy/cut/paste   ctrl+z/y: undo/redo   ctrl+s: save   ctrl+shift+s: save as   p: playtest from cursor";
+
+fn world() -> BBox {
+    crate::world()
+}
+
+/// Something drawn for the editor, which is thrown away and drawn again on every edit
+#[derive(Component)]
+struct EditorSprite;
+
+#[derive(Component)]
+struct EditorStatusText;
+
+/// How many rows fit between the target line and the top of the screen. Same as while playing, so the notes are spaced out the same.
+fn lead_rows(editor: &Editor) -> f32 {
+    editor.chart().lead_time_beats().max(1.0)
+}
+
+/// The cursor row sits on the target line, in the middle of the lane targets
+fn cursor_y() -> f32 {
+    world().bottom() + 0.5 * Arrow::height()
+}
+
+/// Where a row is drawn. Rows can be in between, to find where they start and end.
+pub fn row_y(editor: &Editor, row: f32) -> f32 {
+    let t = (row - editor.cursor_row() as f32) / lead_rows(editor);
+    cursor_y() + t * (world().top() - cursor_y())
+}
+
+/// The row drawn at this height on the screen, which may be in between rows or before the start of the chart
+pub fn row_at(editor: &Editor, y: f32) -> f32 {
+    editor.cursor_row() as f32 + (y - cursor_y()) / (world().top() - cursor_y()) * lead_rows(editor)
+}
+
+/// The row drawn nearest to this height on the screen, if it is not before the start of the chart
+pub fn row_at_y(editor: &Editor, y: f32) -> Option<usize> {
+    let row = row_at(editor, y).round();
+    (row >= 0.0).then_some(row as usize)
+}
+
+fn setup_status_text(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(crate::BASE_FONT_NAME);
+
+    commands.spawn((
+        EditorStatusText,
+        TextBundle {
+            text: Text::from_section(
+                "".to_string(),
+                TextStyle {
+                    font,
+                    font_size: STATUS_FONT_SIZE,
+                    color: STATUS_TEXT_COLOR,
+                },
+            ).with_justify(JustifyText::Right),
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+/// The panel only shows up once the layout is done, so whatever was drawn before then needs drawing again
+fn redraw_on_layout(mut editor: ResMut<Editor>) {
+    editor.set_changed();
+}
+
+fn spawn_bar(commands: &mut Commands, x: f32, y: f32, z: f32, size: Vec2, color: Color) {
+    commands.spawn((
+        EditorSprite,
+        SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(x, y, z),
+                scale: size.extend(1.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+/// The chart is played like any other while playtesting, so the editor gets out of the way
+fn clear_chart(
+    mut commands: Commands,
+    sprite_q: Query<Entity, With<EditorSprite>>,
+) {
+    despawn_sprites(&mut commands, &sprite_q);
+}
+
+fn despawn_sprites(commands: &mut Commands, sprite_q: &Query<Entity, With<EditorSprite>>) {
+    for entity in sprite_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Draws the notes where they sit in the chart, instead of moving them along with a song
+fn draw_chart(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    panel_q: Query<&SongPanel, With<PlayerMarker>>,
+    sprite_q: Query<Entity, With<EditorSprite>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    let Ok(panel) = panel_q.get_single() else {
+        return;
+    };
+
+    despawn_sprites(&mut commands, &sprite_q);
+
+    let first_row = editor.cursor_row();
+    let last_row = first_row + lead_rows(editor.as_ref()).ceil() as usize;
+    let visible = first_row..=last_row;
+
+    let bounds = panel.bounds();
+    let x = bounds.center().x;
+    let grid_z = Layer::Arrows.z();
+
+    // the selection sits behind the grid
+    if let Some(selection) = editor.selection() {
+        let start = (*selection.start()).max(first_row);
+        let end = (*selection.end()).min(last_row);
+        if start <= end {
+            let bottom = row_y(editor.as_ref(), start as f32 - 0.5);
+            let top = row_y(editor.as_ref(), end as f32 + 0.5);
+            let size = Vec2::new(bounds.width(), top - bottom);
+            spawn_bar(&mut commands, x, (top + bottom) / 2.0, grid_z - 1.0, size, SELECTION_COLOR);
+        }
+    }
+
+    let rows_per_beat = editor.chart().rows_per_beat();
+    for row in visible.clone() {
+        let row_in_song = row as u32;
+        let color = if row_in_song.is_multiple_of(rows_per_beat * BEATS_PER_MEASURE) {
+            MEASURE_LINE_COLOR
+        } else if row_in_song.is_multiple_of(rows_per_beat) {
+            BEAT_LINE_COLOR
+        } else {
+            ROW_LINE_COLOR
+        };
+        let size = Vec2::new(bounds.width(), GRID_LINE_HEIGHT);
+        spawn_bar(&mut commands, x, row_y(editor.as_ref(), row as f32), grid_z, size, color);
+    }
+
+    // in front of the lane targets, so the notes on the cursor row can be seen
+    let size = Vec2::new(bounds.width(), CURSOR_LINE_HEIGHT);
+    spawn_bar(&mut commands, x, cursor_y(), Layer::AboveTargets.z(), size, CURSOR_LINE_COLOR);
+
+    for (row, lane) in editor.notes().filter(|(row, _)| visible.contains(row)) {
+        let lane_bounds = panel.lane_bounds(lane);
+        let size = Vec2::new(lane_bounds.width(), Arrow::height());
+        let y = row_y(editor.as_ref(), row as f32);
+        spawn_bar(&mut commands, lane_bounds.center().x, y, Layer::AboveTargets.z() + 1.0, size, lane.colors().base);
+    }
+}
+
+fn update_status_text(
+    editor: Res<Editor>,
+    prompt: Res<SaveAsPrompt>,
+    playtest: Option<Res<Playtest>>,
+    mut text_q: Query<&mut Text, With<EditorStatusText>>,
+) {
+    if !editor.is_changed() && !prompt.is_changed() {
+        return;
+    }
+    let playing = playtest.is_some_and(|playtest| playtest.is_playing());
+
+    let chart = editor.chart();
+    let rows_per_beat = chart.rows_per_beat();
+    let row = editor.cursor_row();
+
+    let mut content = format!(
+        "{}{}   beat {} row {}",
+        chart.chart_name(),
+        if editor.is_dirty() { " (unsaved)" } else { "" },
+        row as u32 / rows_per_beat,
+        row as u32 % rows_per_beat,
+    );
+    if let Some(selection) = editor.selection() {
+        content.push_str(format!("   selected rows {} to {}", selection.start(), selection.end()).as_str());
+    }
+    content.push('\n');
+    if prompt.is_naming() {
+        content.push_str(format!("Save as: {}_", prompt.draft()).as_str());
+    } else {
+        content.push_str(editor.status());
+    }
+    content.push('\n');
+    if playing {
+        content.push_str("p: stop playtesting");
+    } else {
+        content.push_str(HELP_TEXT);
+    }
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = content.clone();
+    }
+}
+
+pub struct EditorViewPlugin;
+impl Plugin for EditorViewPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, setup_status_text
+                .after(super::start_editor)
+                .run_if(is_editing)
+            )
+            .add_systems(OnEnter(LayoutState::Done), redraw_on_layout.run_if(is_editing))
+            .add_systems(OnEnter(SongState::SettingUp::<PlayerMarker>), clear_chart.run_if(is_editing))
+            .add_systems(Update, draw_chart
+                .run_if(is_editing)
+                .run_if(in_state(SongState::NotPlaying::<PlayerMarker>))
+            )
+            .add_systems(Update, update_status_text.run_if(is_editing))
+        ;
+    }
+}
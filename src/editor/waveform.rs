@@ -0,0 +1,271 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use bevy::prelude::*;
+use bevy::tasks::{
+    block_on,
+    poll_once,
+    AsyncComputeTaskPool,
+    Task,
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+use crate::layout::{
+    BBox,
+    Layer,
+    SongPanel,
+};
+use crate::song::{
+    decode_sound_file,
+    DecodedSong,
+    SongState,
+};
+use crate::team_markers::PlayerMarker;
+
+use super::{
+    is_editing,
+    view,
+    Editor,
+};
+
+/// How finely the envelope follows the song
+const PEAKS_PER_SEC: u32 = 100;
+/// Envelopes cached in an older format are built again
+const ENVELOPE_VERSION: u32 = 1;
+/// Cached envelopes sit next to the song, e.g. `song.ogg.peaks.json`
+const CACHE_SUFFIX: &str = "peaks.json";
+
+const WAVEFORM_COLOR: Color = Color::rgba(0.6, 0.7, 0.9, 0.6);
+const WAVEFORM_WIDTH: f32 = 80.0;
+/// Space between the lanes and the waveform
+const WAVEFORM_GAP: f32 = 10.0;
+/// The waveform is drawn as a stack of bars this tall
+const WAVEFORM_STEP: f32 = 4.0;
+
+fn world() -> BBox {
+    crate::world()
+}
+
+/// The loudest part of each slice of the song, so that it can be drawn without decoding it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    /// Size and modified time of the song, to tell when the cached envelope is out of date
+    song_len: u64,
+    song_modified_secs: u64,
+    peaks_per_sec: u32,
+    /// From 0 to 255, the loudest sample in each slice
+    peaks: Vec<u8>,
+}
+impl Envelope {
+    fn from_song(song: &DecodedSong, (song_len, song_modified_secs): (u64, u64)) -> Envelope {
+        let slice_len = (song.sample_rate / PEAKS_PER_SEC).max(1) as usize;
+        let peaks = song.samples
+            .chunks(slice_len)
+            .map(|slice| slice.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs())))
+            .map(|peak| (peak.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8)
+            .collect();
+        Envelope {
+            version: ENVELOPE_VERSION,
+            song_len,
+            song_modified_secs,
+            peaks_per_sec: PEAKS_PER_SEC,
+            peaks,
+        }
+    }
+    fn is_for(&self, stamp: (u64, u64)) -> bool {
+        self.version == ENVELOPE_VERSION && (self.song_len, self.song_modified_secs) == stamp
+    }
+    /// The loudest the song gets in between these times, from 0 to 1
+    fn peak_between(&self, start_secs: f32, end_secs: f32) -> f32 {
+        let rate = self.peaks_per_sec as f32;
+        let start = (start_secs.min(end_secs) * rate).floor().max(0.0) as usize;
+        let end = (start_secs.max(end_secs) * rate).ceil().max(0.0) as usize;
+        self.peaks
+            .get(start..end.min(self.peaks.len()))
+            .and_then(|peaks| peaks.iter().max())
+            .map_or(0.0, |&peak| peak as f32 / u8::MAX as f32)
+    }
+}
+
+/// Size and modified time of the song
+fn song_stamp(path: &Path) -> Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    Ok((metadata.len(), modified))
+}
+
+fn cache_path(song_path: &Path) -> PathBuf {
+    let mut path = song_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(CACHE_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Reads the cached envelope if it is still good, otherwise decodes the song and caches a new one
+fn load_envelope(song_path: &Path) -> Result<Envelope> {
+    let stamp = song_stamp(song_path)?;
+    let cache_path = cache_path(song_path);
+
+    let cached = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Envelope>(text.as_str()).ok())
+        .filter(|envelope| envelope.is_for(stamp));
+    if let Some(envelope) = cached {
+        log::info!("loaded waveform from {}", cache_path.display());
+        return Ok(envelope);
+    }
+
+    log::info!("decoding {} for its waveform", song_path.display());
+    let song = decode_sound_file(song_path)?;
+    let envelope = Envelope::from_song(&song, stamp);
+
+    // without a cache we just decode it again next time
+    let written = serde_json::to_string(&envelope)
+        .context("serializing waveform")
+        .and_then(|text| std::fs::write(&cache_path, text)
+            .with_context(|| format!("writing waveform to {}", cache_path.display()))
+        );
+    match written {
+        Ok(()) => log::info!("cached waveform in {}", cache_path.display()),
+        Err(e) => log::warn!("unable to cache waveform: {e:?}"),
+    }
+    Ok(envelope)
+}
+
+/// The song's waveform, once it is done loading
+#[derive(Resource)]
+#[derive(Default)]
+struct Waveform {
+    loading: Option<Task<Result<Envelope>>>,
+    envelope: Option<Envelope>,
+}
+
+#[derive(Component)]
+struct WaveformSprite;
+
+/// Decoding a whole song takes a while, so it happens off to the side while editing goes on
+fn start_loading_waveform(
+    mut commands: Commands,
+    editor: Res<Editor>,
+) {
+    let mut waveform = Waveform::default();
+    if let Some(sound_file) = editor.chart().sound_file() {
+        let path = Path::new(crate::record::SOUND_ASSET_PATH).join(sound_file);
+        waveform.loading = Some(AsyncComputeTaskPool::get().spawn(async move {
+            load_envelope(path.as_path())
+        }));
+    }
+    commands.insert_resource(waveform);
+}
+
+fn finish_loading_waveform(
+    mut waveform: ResMut<Waveform>,
+) {
+    let Some(task) = waveform.loading.as_mut() else {
+        return;
+    };
+    let Some(loaded) = block_on(poll_once(task)) else {
+        return; // still going
+    };
+    waveform.loading = None;
+    match loaded {
+        Ok(envelope) => waveform.envelope = Some(envelope),
+        Err(e) => log::warn!("unable to show the waveform: {e:?}"),
+    }
+}
+
+fn clear_waveform(
+    mut commands: Commands,
+    sprite_q: Query<Entity, With<WaveformSprite>>,
+) {
+    for entity in sprite_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Draws the waveform next to the lanes, so that the song lines up with the rows it plays on
+fn draw_waveform(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    waveform: Res<Waveform>,
+    panel_q: Query<&SongPanel, With<PlayerMarker>>,
+    sprite_q: Query<Entity, With<WaveformSprite>>,
+) {
+    if !editor.is_changed() && !waveform.is_changed() {
+        return;
+    }
+    let (Some(envelope), Ok(panel)) = (waveform.envelope.as_ref(), panel_q.get_single()) else {
+        return;
+    };
+
+    for entity in sprite_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let chart = editor.chart();
+    // the song starts playing one lead time before the first row arrives
+    let secs_at = |row: f32| (row + chart.lead_time_beats()) * chart.beat_duration_secs();
+
+    let x = panel.bounds().right() + WAVEFORM_GAP + WAVEFORM_WIDTH / 2.0;
+    let steps = (world().height() / WAVEFORM_STEP).ceil() as usize;
+    for step in 0..steps {
+        let bottom = world().bottom() + step as f32 * WAVEFORM_STEP;
+        let top = bottom + WAVEFORM_STEP;
+        let peak = envelope.peak_between(
+            secs_at(view::row_at(editor.as_ref(), bottom)),
+            secs_at(view::row_at(editor.as_ref(), top)),
+        );
+        if peak <= 0.0 {
+            continue;
+        }
+
+        commands.spawn((
+            WaveformSprite,
+            SpriteBundle {
+                transform: Transform {
+                    translation: Vec3::new(x, (top + bottom) / 2.0, Layer::Arrows.z()),
+                    scale: Vec3::new(peak * WAVEFORM_WIDTH, WAVEFORM_STEP, 1.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: WAVEFORM_COLOR,
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+    }
+}
+
+pub struct WaveformPlugin;
+impl Plugin for WaveformPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, start_loading_waveform
+                .after(super::start_editor)
+                .run_if(is_editing)
+            )
+            .add_systems(OnEnter(SongState::SettingUp::<PlayerMarker>), clear_waveform.run_if(is_editing))
+            .add_systems(Update, (
+                    finish_loading_waveform,
+                    draw_waveform
+                        .after(finish_loading_waveform)
+                        .run_if(in_state(SongState::NotPlaying::<PlayerMarker>)),
+            ).run_if(is_editing))
+        ;
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use rand::{
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng,
+    SeedableRng,
+};
+
+use crate::lane::Lane;
+use crate::record::{
+    free_chart_name,
+    Timing,
+};
+use crate::song::{
+    Chart,
+    Note,
+};
+
+/// How many beats each section of a composed chart lasts
+const BEATS_PER_SECTION: u32 = 4;
+
+/// The most voices a polyrhythm can have, one to a lane
+const MAX_VOICES: usize = 4;
+
+/// What kind of notes fill a section of the chart
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SectionKind {
+    /// Single notes running across all four lanes, with only as many jacks as asked for
+    Stream,
+    /// Two lanes, one on each hand, hit one after the other
+    Trill,
+    /// The same lane over and over
+    Jack,
+    /// Each voice of the polyrhythm on its own lane, hitting on its own period
+    Poly,
+}
+
+pub fn parse_section_kind(source: &str) -> Result<SectionKind> {
+    match source.trim().to_lowercase().as_str() {
+        "stream" | "streams" => Ok(SectionKind::Stream),
+        "trill" | "trills" => Ok(SectionKind::Trill),
+        "jack" | "jacks" => Ok(SectionKind::Jack),
+        "poly" => Ok(SectionKind::Poly),
+        other => anyhow::bail!("expected stream, trill, jack or poly, got {other:?}"),
+    }
+}
+
+/// How often each voice of a polyrhythm hits, in rows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polyrhythm {
+    periods: Vec<usize>,
+}
+
+/// Accepts `2:3` or `2:3:5`, like the poly-rhythm charts
+pub fn parse_polyrhythm(source: &str) -> Result<Polyrhythm> {
+    let periods = source
+        .split(':')
+        .map(|period| period.trim().parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if !(2..=MAX_VOICES).contains(&periods.len()) {
+        anyhow::bail!("a polyrhythm needs between 2 and {MAX_VOICES} voices, like 2:3, got {source}");
+    }
+    if let Some(period) = periods.iter().find(|&&period| !(1..=16).contains(&period)) {
+        anyhow::bail!("each voice of a polyrhythm hits every 1 to 16 rows, got {period}");
+    }
+    Ok(Polyrhythm { periods })
+}
+
+/// What to compose, as given on the command line
+#[derive(clap::Args)]
+#[derive(Debug, Clone)]
+pub struct ComposeArgs {
+    /// What to call the new chart, without the .json. Defaults to composed.
+    /// If there is already a chart by that name, a number is added on to the end.
+    #[arg(long, value_parser=crate::editor::parse_chart_name)]
+    pub name: Option<String>,
+
+    /// A song to play along with the chart, from the assets/sounds folder. The notes do not follow it.
+    #[arg(long, value_parser=crate::record::parse_sound_file)]
+    pub sound_file: Option<String>,
+
+    /// The tempo of the chart, in beats per minute
+    #[arg(long, default_value_t = 120.0, value_parser=crate::record::parse_bpm)]
+    pub bpm: f32,
+
+    /// How many chart rows to a beat
+    #[arg(long, default_value_t = 4)]
+    pub subdivisions: u32,
+
+    /// How many sections of four beats to compose
+    #[arg(long, default_value_t = 16)]
+    pub sections: u32,
+
+    /// Which kinds of section to pick from, each section at random: stream, trill, jack or poly
+    #[arg(long, default_value = "stream,trill,poly", value_delimiter = ',', value_parser=parse_section_kind)]
+    pub patterns: Vec<SectionKind>,
+
+    /// How many rows of the streams, trills and jacks have notes, from 0 to 1
+    #[arg(long, default_value_t = 0.75, value_parser=crate::record::quantize::parse_strength)]
+    pub density: f32,
+
+    /// How likely each note of a stream or trill is to be a two note chord, from 0 to 1
+    #[arg(long, default_value_t = 0.1, value_parser=crate::record::quantize::parse_strength)]
+    pub chords: f32,
+
+    /// How likely a stream is to hit the same lane twice in a row, from 0 to 1
+    #[arg(long, default_value_t = 0.0, value_parser=crate::record::quantize::parse_strength)]
+    pub jacks: f32,
+
+    /// The voices layered in poly sections, as how many rows apart each one hits
+    #[arg(long, default_value = "2:3", value_parser=parse_polyrhythm)]
+    pub polyrhythm: Polyrhythm,
+
+    /// Seed for the patterns, to make the same chart again. Random by default.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+/// Writes a chart made up of patterns rather than taken from a song. Does not open a window.
+pub fn run_compose(args: &ComposeArgs) -> Result<()> {
+    let subdivisions = args.subdivisions.max(1);
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut composer = Composer {
+        args,
+        rng: StdRng::seed_from_u64(seed),
+        previous: Vec::new(),
+    };
+
+    let rows_per_section = (BEATS_PER_SECTION * subdivisions) as usize;
+    let mut beats: Vec<Vec<Note>> = Vec::with_capacity(rows_per_section * args.sections as usize);
+    for _ in 0..args.sections {
+        let kind = *args.patterns.choose(&mut composer.rng).expect("at least one kind of section");
+        let section = composer.section(kind, beats.len(), rows_per_section);
+        beats.extend(section.into_iter().map(|lanes| lanes.into_iter().map(Note::new).collect()));
+    }
+
+    let note_count: usize = beats.iter().map(Vec::len).sum();
+    if note_count == 0 {
+        anyhow::bail!("the chart came out without any notes, try a higher --density or other --patterns");
+    }
+
+    let timing = Timing::from_bpm(args.bpm);
+    let base_name = args.name.as_deref().unwrap_or("composed");
+    let chart = Chart::new(
+        free_chart_name(base_name),
+        Some(format!("Composed at {:.2} bpm, seed {seed}", timing.bpm())),
+        timing.beat_duration_secs / subdivisions as f32,
+        timing.lead_time_beats * subdivisions as f32,
+        args.sound_file.clone(),
+        beats,
+    ).with_rows_per_beat(subdivisions);
+
+    let path = chart.save_new()?;
+    // read it back the way the game will, so a chart that would not load is never left looking fine
+    Chart::try_load_from_name(chart.chart_name())?;
+    println!("wrote {note_count} notes to {} with seed {seed}", path.display());
+    Ok(())
+}
+
+/// Writes one section after another, carrying the last lanes across so sections join up
+struct Composer<'a> {
+    args: &'a ComposeArgs,
+    rng: StdRng,
+    /// The lanes of the last row with notes
+    previous: Vec<Lane>,
+}
+impl Composer<'_> {
+    /// The rows of one section, starting `offset` rows into the chart
+    fn section(&mut self, kind: SectionKind, offset: usize, rows: usize) -> Vec<Vec<Lane>> {
+        let section: Vec<Vec<Lane>> = match kind {
+            SectionKind::Stream => (0..rows).map(|_| self.stream_row()).collect(),
+            SectionKind::Trill => self.trill(rows),
+            SectionKind::Jack => self.jack(rows),
+            SectionKind::Poly => self.poly(offset, rows),
+        };
+        if let Some(last) = section.iter().rev().find(|lanes| !lanes.is_empty()) {
+            self.previous.clone_from(last);
+        }
+        section
+    }
+
+    fn has_note(&mut self) -> bool {
+        self.rng.gen_bool(self.args.density as f64)
+    }
+
+    /// Adds a second note on the other hand, now and then
+    fn maybe_chord(&mut self, lane: Lane) -> Vec<Lane> {
+        let mut lanes = vec![lane];
+        if self.rng.gen_bool(self.args.chords as f64) {
+            let other_hand: Vec<Lane> = Lane::all()
+                .iter()
+                .copied()
+                .filter(|other| other.is_left() != lane.is_left())
+                .collect();
+            lanes.extend(other_hand.choose(&mut self.rng));
+        }
+        lanes
+    }
+
+    fn stream_row(&mut self) -> Vec<Lane> {
+        if !self.has_note() {
+            return Vec::new();
+        }
+        let jack = self.rng.gen_bool(self.args.jacks as f64);
+        let lanes: Vec<Lane> = Lane::all()
+            .iter()
+            .copied()
+            .filter(|lane| jack == self.previous.contains(lane))
+            .collect();
+        let lane = *lanes
+            .choose(&mut self.rng)
+            .or_else(|| Lane::all().choose(&mut self.rng))
+            .expect("at least one lane");
+        let lanes = self.maybe_chord(lane);
+        self.previous.clone_from(&lanes);
+        lanes
+    }
+
+    fn trill(&mut self, rows: usize) -> Vec<Vec<Lane>> {
+        let left = *[Lane::L1, Lane::L2].choose(&mut self.rng).expect("a left lane");
+        let right = *[Lane::R1, Lane::R2].choose(&mut self.rng).expect("a right lane");
+        // start on whichever hand did not just play
+        let left_first = !self.previous.first().is_some_and(|lane| lane.is_left());
+        (0..rows)
+            .map(|row| {
+                if !self.has_note() {
+                    return Vec::new();
+                }
+                let lane = if row.is_multiple_of(2) == left_first { left } else { right };
+                // chords land on both lanes of the trill, so it keeps its shape
+                if self.rng.gen_bool(self.args.chords as f64) {
+                    vec![left, right]
+                } else {
+                    vec![lane]
+                }
+            })
+            .collect()
+    }
+
+    fn jack(&mut self, rows: usize) -> Vec<Vec<Lane>> {
+        let lane = *Lane::all().choose(&mut self.rng).expect("at least one lane");
+        // only every other row, so the jack stays playable at high subdivisions
+        (0..rows)
+            .map(|row| {
+                if row.is_multiple_of(2) && self.has_note() {
+                    vec![lane]
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect()
+    }
+
+    /// Voices keep counting from the start of the chart, so a period that does not divide
+    /// the section length carries on across sections instead of restarting on each one
+    fn poly(&mut self, offset: usize, rows: usize) -> Vec<Vec<Lane>> {
+        let mut lanes = Lane::all().to_vec();
+        lanes.shuffle(&mut self.rng);
+        let voices: Vec<(usize, Lane)> = self.args.polyrhythm.periods
+            .iter()
+            .copied()
+            .zip(lanes)
+            .collect();
+        (offset..offset + rows)
+            .map(|row| {
+                voices
+                    .iter()
+                    .filter(|(period, _)| row.is_multiple_of(*period))
+                    .map(|&(_, lane)| lane)
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use rand::{
+    rngs::StdRng,
+    seq::SliceRandom,
+};
+
+use crate::lane::Lane;
+
+/// How lanes are picked for one note after another
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LanePattern {
+    /// L1, L2, R1, R2, then back to L1
+    Stairs,
+    /// Left hand, then right hand, then left again, in either lane of that hand
+    Alternate,
+    /// Any lane at all
+    Random,
+}
+
+pub fn parse_lane_pattern(source: &str) -> Result<LanePattern> {
+    match source.trim().to_lowercase().as_str() {
+        "stairs" => Ok(LanePattern::Stairs),
+        "alternate" => Ok(LanePattern::Alternate),
+        "random" => Ok(LanePattern::Random),
+        other => anyhow::bail!("expected stairs, alternate or random, got {other:?}"),
+    }
+}
+
+/// What the lanes of a generated chart should look like
+#[derive(Debug, Copy, Clone)]
+pub struct LaneRules {
+    pub pattern: LanePattern,
+    /// Notes at least this strong, from 0 to 1, become two note chords. `None` for no chords at all
+    pub chord_strength: Option<f32>,
+    /// Whether a note may come down the same lane as a note on the row just before it
+    pub allow_jacks: bool,
+}
+
+/// Picks the lanes for each row of notes in turn, following the rules
+pub struct LaneAssigner {
+    rules: LaneRules,
+    rng: StdRng,
+    /// How many rows of notes so far
+    count: usize,
+    /// The lanes of the row of notes just before
+    previous: Vec<Lane>,
+}
+impl LaneAssigner {
+    pub fn new(rules: LaneRules, rng: StdRng) -> LaneAssigner {
+        LaneAssigner {
+            rules,
+            rng,
+            count: 0,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Lanes that the rules would allow next to the row before
+    fn allowed(&self, lanes: impl Iterator<Item = Lane>) -> Vec<Lane> {
+        lanes
+            .filter(|lane| self.rules.allow_jacks || !self.previous.contains(lane))
+            .collect()
+    }
+
+    /// The main lane for the next row
+    fn next_lane(&mut self) -> Lane {
+        let preferred: Vec<Lane> = match self.rules.pattern {
+            LanePattern::Stairs => vec![Lane::all()[self.count % Lane::all().len()]],
+            LanePattern::Alternate => {
+                let left = self.count.is_multiple_of(2);
+                Lane::all().iter().copied().filter(|lane| lane.is_left() == left).collect()
+            }
+            LanePattern::Random => Lane::all().to_vec(),
+        };
+
+        // fall back to any lane the rules allow, if the pattern runs into a jack
+        let allowed = self.allowed(preferred.iter().copied());
+        let allowed = if allowed.is_empty() {
+            self.allowed(Lane::all().iter().copied())
+        } else {
+            allowed
+        };
+        allowed
+            .choose(&mut self.rng)
+            .or_else(|| preferred.choose(&mut self.rng))
+            .copied()
+            .expect("at least one lane")
+    }
+
+    /// The lanes for the next row of notes, given how strong the note is from 0 to 1
+    pub fn next_row(&mut self, strength: f32) -> Vec<Lane> {
+        let lane = self.next_lane();
+        let mut lanes = vec![lane];
+
+        if self.rules.chord_strength.is_some_and(|chord_strength| strength >= chord_strength) {
+            // the other hand plays the second note, so the chord can actually be hit
+            let other_hand = self.allowed(Lane::all().iter().copied().filter(|other| other.is_left() != lane.is_left()));
+            if let Some(&second) = other_hand.choose(&mut self.rng) {
+                lanes.push(second);
+            }
+        }
+
+        self.count += 1;
+        self.previous.clone_from(&lanes);
+        lanes
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+use rand::SeedableRng;
+
+mod compose;
+mod lanes;
+mod onsets;
+mod tempo;
+
+use crate::record::{
+    free_chart_name,
+    Timing,
+};
+use crate::song::{
+    decode_sound_file,
+    Chart,
+    Note,
+};
+
+pub use compose::{
+    run_compose,
+    ComposeArgs,
+};
+use lanes::{
+    LaneAssigner,
+    LanePattern,
+    LaneRules,
+};
+
+/// What to generate a chart from, as given on the command line
+#[derive(clap::Args)]
+#[derive(Debug, Clone)]
+pub struct GenerateArgs {
+    /// The song to chart, a file in the assets/sounds folder. Only ogg and wav songs can be decoded.
+    #[arg(value_parser=crate::record::parse_sound_file)]
+    pub sound_file: String,
+
+    /// What to call the new chart, without the .json. Defaults to the song's file name.
+    /// If there is already a chart by that name, a number is added on to the end.
+    #[arg(long, value_parser=crate::editor::parse_chart_name)]
+    pub name: Option<String>,
+
+    /// The tempo of the song, in beats per minute. By default, the tempo is worked out from the song.
+    #[arg(long, value_parser=crate::record::parse_bpm)]
+    pub bpm: Option<f32>,
+
+    /// How many chart rows to a beat. Each note is snapped to the nearest row.
+    #[arg(long, default_value_t = 2)]
+    pub subdivisions: u32,
+
+    /// How many times louder than the music around it a sound needs to be to become a note.
+    /// Lower numbers give more notes.
+    #[arg(long, default_value_t = 1.5)]
+    pub sensitivity: f32,
+
+    /// How lanes follow each other: stairs, alternate or random.
+    #[arg(long, default_value = "alternate", value_parser=lanes::parse_lane_pattern)]
+    pub pattern: LanePattern,
+
+    /// Notes at least this strong, from 0 to 1 compared to the strongest note, become two note chords.
+    /// At 1, only the strongest note does. Use --no-chords for none at all.
+    #[arg(long, default_value_t = 0.8, value_parser=crate::record::quantize::parse_strength)]
+    pub chord_strength: f32,
+
+    /// Write single notes only, never chords.
+    #[arg(long, conflicts_with = "chord_strength")]
+    pub no_chords: bool,
+
+    /// Let notes come down the same lane twice in a row.
+    #[arg(long)]
+    pub allow_jacks: bool,
+
+    /// Seed for picking lanes, to make the same chart again. Random by default.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+/// Writes a draft chart for the song, with a note on every onset the song has. Does not open a window.
+pub fn run_generate(args: &GenerateArgs) -> Result<()> {
+    let path = Path::new(crate::record::SOUND_ASSET_PATH).join(args.sound_file.as_str());
+    println!("decoding {}...", path.display());
+    let song = decode_sound_file(path.as_path())?;
+
+    let flux = onsets::spectral_flux(&song);
+    let onsets = onsets::pick_onsets(&flux, args.sensitivity);
+    if onsets.is_empty() {
+        anyhow::bail!("found no onsets in {}, try a lower --sensitivity", args.sound_file);
+    }
+    let Some(tempo) = tempo::estimate_tempo(&flux, args.bpm) else {
+        anyhow::bail!("unable to work out the tempo of {}, try giving it with --bpm", args.sound_file);
+    };
+    println!("found {} onsets, at {:.2} bpm with a beat {:.3}s in", onsets.len(), tempo.bpm(), tempo.first_beat_secs);
+
+    let timing = Timing::from_first_beat(tempo.beat_duration_secs, tempo.first_beat_secs);
+    let subdivisions = args.subdivisions.max(1);
+
+    // the strongest onset on each row
+    let mut rows: BTreeMap<usize, f32> = BTreeMap::new();
+    for onset in onsets.iter() {
+        let row = (timing.beat_at(onset.secs) * subdivisions as f32).round();
+        if row < 0.0 {
+            continue; // before the first row of the chart
+        }
+        let strength = rows.entry(row as usize).or_default();
+        *strength = strength.max(onset.strength);
+    }
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut assigner = LaneAssigner::new(
+        LaneRules {
+            pattern: args.pattern,
+            chord_strength: (!args.no_chords).then_some(args.chord_strength),
+            allow_jacks: args.allow_jacks,
+        },
+        rand::rngs::StdRng::seed_from_u64(seed),
+    );
+
+    let row_count = rows.keys().last().map_or(0, |&last| last + 1);
+    let mut beats: Vec<Vec<Note>> = vec![Vec::new(); row_count];
+    for (&row, &strength) in rows.iter() {
+        beats[row] = assigner
+            .next_row(strength)
+            .into_iter()
+            .map(Note::new)
+            .collect();
+    }
+
+    let base_name = args.name.clone().unwrap_or_else(|| {
+        Path::new(args.sound_file.as_str())
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("generated")
+            .to_string()
+    });
+    let chart = Chart::new(
+        free_chart_name(base_name.as_str()),
+        Some(format!("Generated from {} at {:.2} bpm, seed {seed}", args.sound_file, timing.bpm())),
+        timing.beat_duration_secs / subdivisions as f32,
+        timing.lead_time_beats * subdivisions as f32,
+        Some(args.sound_file.clone()),
+        beats,
+    ).with_rows_per_beat(subdivisions);
+
+    let path = chart.save_new()?;
+    println!("wrote {} notes to {} with seed {seed}", rows.len(), path.display());
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use crate::song::DecodedSong;
+
+/// Samples in each slice of the song we look at. Must be a power of two for the FFT
+const FRAME_LEN: usize = 1024;
+/// Samples between the starts of consecutive slices
+const HOP_LEN: usize = 512;
+/// How many slices either side a peak in the flux needs to stand out from
+const PEAK_RADIUS: usize = 3;
+/// How many slices either side to take the typical flux from
+const MEDIAN_RADIUS: usize = 16;
+/// Onsets closer together than this are the same sound
+const MIN_ONSET_GAP_SECS: f32 = 0.05;
+
+/// When a new sound starts in the song, and how strongly, from 0 to 1 compared to the strongest one
+#[derive(Debug, Copy, Clone)]
+pub struct Onset {
+    pub secs: f32,
+    pub strength: f32,
+}
+
+/// How much the spectrum grows from each slice of the song to the next. New sounds show up as sharp peaks.
+#[derive(Debug, Clone)]
+pub struct SpectralFlux {
+    /// Slices per second
+    pub frame_rate: f32,
+    /// Seconds into the song of the middle of the first slice
+    pub first_frame_secs: f32,
+    pub flux: Vec<f32>,
+}
+impl SpectralFlux {
+    pub fn secs_at(&self, frame: f32) -> f32 {
+        self.first_frame_secs + frame / self.frame_rate
+    }
+}
+
+/// In place radix 2 FFT
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // bit reversed order, so that the butterflies can work in place
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -std::f32::consts::TAU / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let (b_re, b_im) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                re[b] = re[a] - b_re;
+                im[b] = im[a] - b_im;
+                re[a] += b_re;
+                im[a] += b_im;
+            }
+        }
+        len *= 2;
+    }
+}
+
+pub fn spectral_flux(song: &DecodedSong) -> SpectralFlux {
+    let window: Vec<f32> = (0..FRAME_LEN)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FRAME_LEN as f32).cos())
+        .collect();
+
+    let mut previous = vec![0.0; FRAME_LEN / 2];
+    let mut flux = Vec::new();
+    let mut re = vec![0.0; FRAME_LEN];
+    let mut im = vec![0.0; FRAME_LEN];
+
+    let mut start = 0;
+    while start + FRAME_LEN <= song.samples.len() {
+        for (i, (re, im)) in re.iter_mut().zip(im.iter_mut()).enumerate() {
+            *re = song.samples[start + i] * window[i];
+            *im = 0.0;
+        }
+        fft(re.as_mut_slice(), im.as_mut_slice());
+
+        // log compressed, so that quiet parts of the song still have onsets
+        let mut total = 0.0;
+        for (bin, previous) in previous.iter_mut().enumerate() {
+            let magnitude = (1.0 + 100.0 * re[bin].hypot(im[bin])).ln();
+            total += (magnitude - *previous).max(0.0);
+            *previous = magnitude;
+        }
+        flux.push(total);
+
+        start += HOP_LEN;
+    }
+    // the first slice grows from silence, which is not an onset
+    if let Some(first) = flux.first_mut() {
+        *first = 0.0;
+    }
+
+    SpectralFlux {
+        frame_rate: song.sample_rate as f32 / HOP_LEN as f32,
+        first_frame_secs: FRAME_LEN as f32 / 2.0 / song.sample_rate as f32,
+        flux,
+    }
+}
+
+fn median(values: &[f32]) -> f32 {
+    let mut values = values.to_vec();
+    values.sort_by(f32::total_cmp);
+    values.get(values.len() / 2).copied().unwrap_or(0.0)
+}
+
+/// Peaks in the flux that stand out from the flux around them by `sensitivity` times or more
+pub fn pick_onsets(flux: &SpectralFlux, sensitivity: f32) -> Vec<Onset> {
+    let values = flux.flux.as_slice();
+    let loudest = values.iter().copied().fold(0.0f32, f32::max);
+    if loudest <= 0.0 {
+        return Vec::new();
+    }
+    // flat stretches would never stand out from their median of zero
+    let floor = loudest * 0.05;
+
+    let mut onsets: Vec<Onset> = Vec::new();
+    for (frame, &value) in values.iter().enumerate() {
+        let near = &values[frame.saturating_sub(PEAK_RADIUS)..(frame + PEAK_RADIUS + 1).min(values.len())];
+        if near.iter().any(|&other| other > value) {
+            continue; // not the peak
+        }
+        let around = &values[frame.saturating_sub(MEDIAN_RADIUS)..(frame + MEDIAN_RADIUS + 1).min(values.len())];
+        if value < floor || value < median(around) * sensitivity {
+            continue;
+        }
+
+        let onset = Onset {
+            secs: flux.secs_at(frame as f32),
+            strength: value / loudest,
+        };
+        match onsets.last_mut() {
+            Some(last) if onset.secs - last.secs < MIN_ONSET_GAP_SECS => {
+                if onset.strength > last.strength {
+                    *last = onset;
+                }
+            }
+            _ => onsets.push(onset),
+        }
+    }
+    onsets
+}
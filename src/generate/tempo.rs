@@ -0,0 +1,109 @@
+use super::onsets::SpectralFlux;
+
+/// The range of tempos we look for. Anything outside is taken to be a half or double of one inside.
+const MIN_BPM: f32 = 70.0;
+const MAX_BPM: f32 = 180.0;
+/// Tempos are weighed towards this one, so that we pick the tempo over a half or double of it
+const PREFERRED_BPM: f32 = 120.0;
+/// How quickly the weight falls off away from the preferred tempo, in octaves
+const PREFERENCE_WIDTH: f32 = 1.0;
+
+/// The tempo of the song, and where its beats fall
+#[derive(Debug, Copy, Clone)]
+pub struct TempoEstimate {
+    pub beat_duration_secs: f32,
+    /// When one of the beats plays, in seconds from the start of the song
+    pub first_beat_secs: f32,
+}
+impl TempoEstimate {
+    pub fn bpm(&self) -> f32 {
+        60.0 / self.beat_duration_secs
+    }
+}
+
+/// How much the flux looks like itself shifted over by `lag` slices
+fn autocorrelation(flux: &[f32], lag: usize) -> f32 {
+    if lag >= flux.len() {
+        return 0.0;
+    }
+    let sum: f32 = flux
+        .iter()
+        .zip(flux[lag..].iter())
+        .map(|(a, b)| a * b)
+        .sum();
+    sum / (flux.len() - lag) as f32
+}
+
+/// The flux somewhere in between two slices
+fn flux_at(flux: &[f32], frame: f32) -> f32 {
+    let i = frame.floor() as usize;
+    let t = frame - frame.floor();
+    match (flux.get(i), flux.get(i + 1)) {
+        (Some(a), Some(b)) => a + (b - a) * t,
+        (Some(a), None) => *a,
+        _ => 0.0,
+    }
+}
+
+/// The beat period, in slices, with the most regular repeats in the flux
+fn estimate_period(flux: &[f32], frame_rate: f32) -> Option<f32> {
+    let min_lag = (60.0 * frame_rate / MAX_BPM).floor() as usize;
+    let max_lag = (60.0 * frame_rate / MIN_BPM).ceil() as usize;
+
+    let weight = |lag: f32| {
+        let bpm = 60.0 * frame_rate / lag;
+        let octaves = (bpm / PREFERRED_BPM).log2() / PREFERENCE_WIDTH;
+        (-0.5 * octaves * octaves).exp()
+    };
+    let scores: Vec<f32> = (min_lag..=max_lag + 1)
+        .map(|lag| autocorrelation(flux, lag) * weight(lag as f32))
+        .collect();
+
+    let best = (1..scores.len() - 1)
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))?;
+    if scores[best] <= 0.0 {
+        return None;
+    }
+
+    // a parabola through the best lag and its neighbours finds the peak in between slices
+    let (before, at, after) = (scores[best - 1], scores[best], scores[best + 1]);
+    let curve = before - 2.0 * at + after;
+    let shift = if curve < 0.0 { 0.5 * (before - after) / curve } else { 0.0 };
+    Some((min_lag + best) as f32 + shift.clamp(-0.5, 0.5))
+}
+
+/// Where the beats fall, as the offset with the most flux on the beats
+fn estimate_phase(flux: &[f32], period: f32) -> f32 {
+    const PHASE_STEPS: usize = 64;
+    let on_beat = |phase: f32| {
+        let beats = ((flux.len() as f32 - phase) / period).max(0.0) as usize;
+        (0..beats).map(|beat| flux_at(flux, phase + beat as f32 * period)).sum::<f32>()
+    };
+    (0..PHASE_STEPS)
+        .map(|step| step as f32 / PHASE_STEPS as f32 * period)
+        .map(|phase| (phase, on_beat(phase)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0.0, |(phase, _)| phase)
+}
+
+/// Guesses the tempo from how regularly the song repeats. Give `bpm` to skip the guess, and only find where the beats fall.
+pub fn estimate_tempo(flux: &SpectralFlux, bpm: Option<f32>) -> Option<TempoEstimate> {
+    let values = flux.flux.as_slice();
+    if values.is_empty() {
+        return None;
+    }
+    // only the changes in flux repeat with the beat
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let centered: Vec<f32> = values.iter().map(|value| value - mean).collect();
+
+    let period = match bpm {
+        Some(bpm) => 60.0 * flux.frame_rate / bpm,
+        None => estimate_period(centered.as_slice(), flux.frame_rate)?,
+    };
+    let phase = estimate_phase(values, period);
+
+    Some(TempoEstimate {
+        beat_duration_secs: period / flux.frame_rate,
+        first_beat_secs: flux.secs_at(phase),
+    })
+}
@@ -8,10 +8,17 @@ use crate::keycode_serde;
 use crate::song::ArrowSpawner;
 use crate::user_settings::UserSettings;
 use crate::lane::Lane;
+use crate::coop::CoopMatch;
+use crate::remote::{
+    not_spectating,
+    playing_locally,
+};
 use crate::team_markers::{
     Marker,
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
 };
 
 /// Represents a user attempting to complete the note in a lane.
@@ -71,6 +78,15 @@ impl std::default::Default for LaneHitControls {
     }
 }
 impl LaneHitControls {
+    /// Keys for the second user at the keyboard in a hot-seat match, out of the way of the default keys
+    pub fn second_player() -> Self {
+        Self {
+            lane_hit_L1: KeyCode::KeyJ,
+            lane_hit_L2: KeyCode::KeyK,
+            lane_hit_R1: KeyCode::KeyL,
+            lane_hit_R2: KeyCode::Semicolon,
+        }
+    }
     pub fn keycode(&self, lane: Lane) -> KeyCode {
         match lane {
             Lane::L1 => self.lane_hit_L1,
@@ -81,12 +97,14 @@ impl LaneHitControls {
     }
 }
 
-fn listen_for_input(
+/// Turns key presses into lane hits for team `T`, using that team's keymap
+fn listen_for_input<T: Marker>(
     time: Res<Time>,
     settings: Res<UserSettings>,
     keys: Res<ButtonInput<KeyCode>>,
-    spawner: Query<&ArrowSpawner<PlayerMarker>>,
-    mut lane_hit_events: EventWriter<LaneHit>,
+    spawner: Query<&ArrowSpawner<T>>,
+    coop: Option<Res<CoopMatch>>,
+    mut lane_hit_events: EventWriter<RawLaneHit<T>>,
 ) {
     let now = time.elapsed().as_secs_f32();
 
@@ -94,18 +112,19 @@ fn listen_for_input(
         return; // nothing to do
     };
 
-    let keymap = &settings.keybindings.lane_hit_keymap;
+    let keymap = settings.keybindings.lane_hit_keymap_for(T::team());
 
     Lane::all()
         .iter()
         .map(|&lane| (lane, keymap.keycode(lane)))
         .filter(|(_lane, keycode)| keys.just_pressed(*keycode))
-        .map(|(lane, _keycode)| LaneHit {
+        // in a co-op match, the partner's lanes are played from their keyboard
+        .filter(|(lane, _keycode)| T::is_remote() || coop.as_ref().is_none_or(|coop| coop.owns(*lane)))
+        .map(|(lane, _keycode)| RawLaneHit::<T>::from(
             lane,
-            beat: spawner.curr_beat(),
-            time_of_hit: now,
-            _team: PlayerMarker{}
-        })
+            spawner.curr_beat(),
+            now,
+        ))
         .for_each(|ev| {
             log::debug!("Sending lane hit event");
             lane_hit_events.send(ev);
@@ -119,7 +138,10 @@ impl Plugin for InputPlugin {
         app
             .add_event::<LaneHit>()
             .add_event::<RemoteLaneHit>()
-            .add_systems(PreUpdate, listen_for_input) // important that input happens the frame it's detected
+            .add_event::<RawLaneHit<Enemy2Marker>>()
+            .add_event::<RawLaneHit<Enemy3Marker>>()
+            .add_systems(PreUpdate, listen_for_input::<PlayerMarker>.run_if(not_spectating)) // important that input happens the frame it's detected
+            .add_systems(PreUpdate, listen_for_input::<EnemyMarker>.run_if(playing_locally))
         ;
     }
 }
@@ -14,10 +14,7 @@ use crate::team_markers::{
 use crate::song::{
     Arrow,
 };
-use crate::input::{
-    RawLaneHit,
-    LaneHit
-};
+use crate::input::RawLaneHit;
 
 
 // these are all in fractions of a beat
@@ -87,6 +84,15 @@ impl SuccessGrade {
             Good | Fair => false,
         }
     }
+    /// How much this grade adds to the score
+    pub fn points(self) -> u32 {
+        use SuccessGrade::*;
+        match self {
+            Perfect => 3,
+            Good => 2,
+            Fair => 1,
+        }
+    }
 }
 
 #[derive(Debug,Copy,Clone,Deserialize,Serialize)]
@@ -122,7 +128,7 @@ impl JudgementSettings {
             fair_cutoff:    DEFAULT_FAIR_CUTOFF,
         }
     }
-    pub fn judge(&self, lane_hit: &LaneHit, arrow: &Arrow) -> Grade {
+    pub fn judge<T: Marker>(&self, lane_hit: &RawLaneHit<T>, arrow: &Arrow) -> Grade {
         let hit_time = lane_hit.beat();
         let arrival_time = arrow.arrival_beat();
         let diff = (arrival_time - hit_time).abs();
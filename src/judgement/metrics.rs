@@ -36,6 +36,9 @@ pub struct SongMetrics {
     /// Of the incorrect hits, how many were late?
     late: u32,
 
+    /// Points from correct hits, better grades are worth more.
+    score: u32,
+
     /// Number of 'perfect' grades in a row.
     streak: u32,
     /// True if the last event we saw broke the streak.
@@ -54,6 +57,7 @@ impl SongMetrics {
             dropped_notes: 0,
             early: 0,
             late: 0,
+            score: 0,
             streak: 0,
             just_broke_streak: false,
         }
@@ -68,6 +72,10 @@ impl SongMetrics {
     pub fn success_arrows(&self) -> u32 {
         self.correct_hits
     }
+    /// Points earned so far this song. Used to decide who won a round.
+    pub fn score(&self) -> u32 {
+        self.score
+    }
     /// Number of consecutive arrows the user has gotten correct. 0 if the last hit was incorrect.
     pub fn streak(&self) -> u32 {
         self.streak
@@ -105,6 +113,8 @@ pub fn update_metrics(
         metrics.missfires      += 0;
         metrics.dropped_notes  += 0;
 
+        metrics.score += correct_hit.grade.points();
+
         if correct_hit.grade.is_perfect() {
             metrics.streak += 1;
         } else {
@@ -181,7 +191,7 @@ impl Plugin for MetricsPlugin {
         app
             .insert_resource(SongMetrics::new())
             .add_systems(Update, update_metrics
-                                 .after(super::judge_lane_hits)
+                                 .after(super::judge_lane_hits::<PlayerMarker>)
              )
             // reset the metrics when we start a song
             .add_systems(OnEnter(SongState::SettingUp::<PlayerMarker>), reset_metrics) 
@@ -4,13 +4,19 @@ pub mod grading;
 use bevy::prelude::*;
 
 use crate::team_markers::{
+    Marker,
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
 };
 
 use crate::song::Arrow;
 use crate::layout::SongPanel;
-use crate::input::LaneHit;
+use crate::input::RawLaneHit;
+use crate::remote::playing_locally;
+use crate::coop::CoopMatch;
+use crate::attacks::ActiveAttacks;
 
 pub use metrics::SongMetrics;
 
@@ -29,19 +35,24 @@ pub use grading::{
 ///   -> CorrectHitEvent
 ///   -> IncorrectHitEvent
 ///   -> MissfireEvent
-fn judge_lane_hits(
+/// Only ever judges the arrows on team `T`'s panel.
+fn judge_lane_hits<T: Marker>(
     // consumes input events
-    mut input_events: EventReader<LaneHit>,
+    mut input_events: EventReader<RawLaneHit<T>>,
 
     // needed to do the judgment
-    mut arrow_q: Query<(&mut Arrow, &Transform)>,
+    mut arrow_q: Query<(&mut Arrow, &Transform), With<T>>,
+    attacks_q: Query<&ActiveAttacks, With<T>>,
     judgement: Res<JudgementSettings>,
 
     // outputs one of the judgement events
-    mut correct_arrow_events: EventWriter<CorrectHitEvent>,
-    mut incorrect_arrow_events: EventWriter<IncorrectHitEvent>,
-    mut missfire_events: EventWriter<MissfireEvent>,
+    mut correct_arrow_events: EventWriter<RawCorrectHitEvent<T>>,
+    mut incorrect_arrow_events: EventWriter<RawIncorrectHitEvent<T>>,
+    mut missfire_events: EventWriter<RawMissfireEvent<T>>,
 ) {
+    // while mirrored, arrows are hit in the lane they are drawn in rather than the one in the chart
+    let drawn_lane = |lane| attacks_q.get_single().map_or(lane, |attacks| attacks.drawn_lane(lane));
+
     for lane_hit in input_events.read() {
                
         // ---------------------------------------------- 
@@ -53,7 +64,7 @@ fn judge_lane_hits(
             .iter_mut()
             
             // only consider arrows in the lane that was hit
-            .filter(|(arrow, _)| arrow.lane() == lane_hit.lane())
+            .filter(|(arrow, _)| drawn_lane(arrow.lane()) == lane_hit.lane())
 
             // Get the absolute arrival time of each
             .map(|(arrow, transform)| {
@@ -79,7 +90,7 @@ fn judge_lane_hits(
         // so we can send that off now and skip to the next lane hit
         let Some((mut arrow, transform, _time_diff)) = search_result else {
             log::debug!("No arrow found, sending a missfire event");
-            missfire_events.send(MissfireEvent {
+            missfire_events.send(RawMissfireEvent {
                 lane_hit: lane_hit.clone()
             });
             continue;
@@ -103,7 +114,7 @@ fn judge_lane_hits(
                 log::debug!("marking arrow as completed");
                 arrow.mark_completed();
                 log::debug!("sending correct hit event");
-                correct_arrow_events.send(CorrectHitEvent {
+                correct_arrow_events.send(RawCorrectHitEvent {
                     lane_hit: lane_hit.clone(),
                     arrow_pos: transform.translation,
                     grade,
@@ -111,7 +122,7 @@ fn judge_lane_hits(
             }
             grading::Grade::Fail(grade) => {
                 log::debug!("sending incorrect hit event");
-                incorrect_arrow_events.send(IncorrectHitEvent {
+                incorrect_arrow_events.send(RawIncorrectHitEvent {
                     lane_hit: lane_hit.clone(),
                     grade,
                 });
@@ -133,15 +144,20 @@ pub struct DroppedNoteEvent {
     arrow: Arrow,
 }
 impl DroppedNoteEvent {
+    pub fn new(arrow: Arrow) -> DroppedNoteEvent {
+        DroppedNoteEvent { arrow }
+    }
     /// The arrow that was never hit.
     pub fn arrow(&self) -> &Arrow {
         &self.arrow
     }
 }
 
-/// Despawns old arrows if they fall out of the screen and emits `DroppedNoteEvent`
+/// Despawns old arrows if they fall out of the screen and emits `DroppedNoteEvent`.
+/// In a co-op match, the partner says which of their notes were dropped.
 fn emit_dropped_notes(
     mut events: EventWriter<DroppedNoteEvent>,
+    coop: Option<Res<CoopMatch>>,
     panel: Query<&SongPanel, With<PlayerMarker>>,
     mut query: Query<(&Transform, &mut Arrow), With<PlayerMarker>>
 ) {
@@ -154,6 +170,7 @@ fn emit_dropped_notes(
             y < panel.arrow_drop_line_y()
         })
         .filter(|(_, arrow)| arrow.status().is_pending())
+        .filter(|(_, arrow)| coop.as_ref().is_none_or(|coop| coop.owns(arrow.lane())))
         .for_each(|(_, mut arrow)| {
             log::debug!("emitting DroppedNoteEvent");
             events.send(DroppedNoteEvent {
@@ -177,12 +194,22 @@ impl Plugin for JudgementPlugin {
             .add_event::<RawIncorrectHitEvent::<EnemyMarker>>()
             .add_event::<RawMissfireEvent::<EnemyMarker>>()
 
+            .add_event::<RawCorrectHitEvent::<Enemy2Marker>>()
+            .add_event::<RawIncorrectHitEvent::<Enemy2Marker>>()
+            .add_event::<RawMissfireEvent::<Enemy2Marker>>()
+
+            .add_event::<RawCorrectHitEvent::<Enemy3Marker>>()
+            .add_event::<RawIncorrectHitEvent::<Enemy3Marker>>()
+            .add_event::<RawMissfireEvent::<Enemy3Marker>>()
+
             .add_event::<DroppedNoteEvent>()
 
             .insert_resource::<JudgementSettings>(JudgementSettings::new())
             
             // Add the systems
-            .add_systems(Update, judge_lane_hits)
+            .add_systems(Update, judge_lane_hits::<PlayerMarker>)
+            // in a hot-seat match, nobody else is judging the enemy's lane hits for us
+            .add_systems(Update, judge_lane_hits::<EnemyMarker>.run_if(playing_locally))
             .add_systems(Update, emit_dropped_notes)
             
             // Add the plugins
@@ -48,6 +48,20 @@ impl Lane {
             },
         }
     }
+    /// Whether the lane is on the left half of the panel, i.e. L1 or L2
+    pub fn is_left(self) -> bool {
+        matches!(self, Lane::L1 | Lane::L2)
+    }
+    /// The lane on the other side of the panel
+    pub fn mirrored(self) -> Lane {
+        use Lane::*;
+        match self {
+            L1 => R2,
+            L2 => R1,
+            R1 => L2,
+            R2 => L1,
+        }
+    }
     pub fn as_str(self) -> &'static str {
         use Lane::*;
         match self {
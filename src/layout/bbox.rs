@@ -55,6 +55,11 @@ impl BBox {
     pub fn height(&self) -> f32 {
         self.size().y
     }
+    /// Whether the point is inside the box, looking only at x and y
+    pub fn contains(&self, point: Vec2) -> bool {
+        (self.left()..=self.right()).contains(&point.x)
+            && (self.bottom()..=self.top()).contains(&point.y)
+    }
     pub fn to_rectangle(&self) -> Rectangle {
         Rectangle::new(self.width(), self.height())
     }
@@ -15,6 +15,9 @@ pub use song_panel::SongPanel;
 use crate::team_markers::{
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
 };
 
 #[derive(Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash,Default)]
@@ -25,27 +28,42 @@ pub enum LayoutState {
     Done
 }
 
+fn spawn_panel<T: Marker>(commands: &mut Commands, bounds: BBox) {
+    commands.spawn((
+        Name::new(format!("song-panel-{}", T::as_str())),
+        T::marker(),
+        SongPanel::new(bounds)
+    ));
+}
+
 fn setup_layout(
     mut commands: Commands,
+    cli: Res<crate::CliArgs>,
     mut state: ResMut<NextState<LayoutState>>,
 ) {
     let bounds = crate::world();
-    let [player_bounds, _, enemy_bounds] = bounds.split_horizontal([0.4, 0.2, 0.4]);
-
-    // create the player song panel
-    let player_panel = SongPanel::new(player_bounds);
-    commands.spawn((
-        Name::new("song-panel-player"),
-        PlayerMarker{},
-        player_panel
-    ));
 
-    let enemy_panel = SongPanel::new(enemy_bounds);
-    commands.spawn((
-        Name::new("song-panel-enemy"),
-        EnemyMarker{},
-        enemy_panel
-    ));
+    // the player keeps the same panel no matter how many people are playing, the remote users share the rest
+    match cli.players {
+        2 => {
+            let [player_bounds, _, enemy_bounds] = bounds.split_horizontal([0.4, 0.2, 0.4]);
+            spawn_panel::<PlayerMarker>(&mut commands, player_bounds);
+            spawn_panel::<EnemyMarker>(&mut commands, enemy_bounds);
+        }
+        3 => {
+            let [player_bounds, _, enemy_bounds, enemy2_bounds] = bounds.split_horizontal([0.4, 0.1, 0.25, 0.25]);
+            spawn_panel::<PlayerMarker>(&mut commands, player_bounds);
+            spawn_panel::<EnemyMarker>(&mut commands, enemy_bounds);
+            spawn_panel::<Enemy2Marker>(&mut commands, enemy2_bounds);
+        }
+        _ => {
+            let [player_bounds, _, enemy_bounds, enemy2_bounds, enemy3_bounds] = bounds.split_horizontal([0.4, 0.06, 0.18, 0.18, 0.18]);
+            spawn_panel::<PlayerMarker>(&mut commands, player_bounds);
+            spawn_panel::<EnemyMarker>(&mut commands, enemy_bounds);
+            spawn_panel::<Enemy2Marker>(&mut commands, enemy2_bounds);
+            spawn_panel::<Enemy3Marker>(&mut commands, enemy3_bounds);
+        }
+    }
     
     // done with laying out, we set this so that now the game objects can spawn in
     state.set(LayoutState::Done);
@@ -11,9 +11,15 @@ mod team_markers;
 mod remote;
 mod widgets;
 mod selector_menu;
+mod attacks;
+mod series;
+mod coop;
+mod chat;
+mod editor;
+mod metronome;
+mod generate;
 
 use std::path::PathBuf;
-use std::net::IpAddr;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 use anyhow::Result;
@@ -25,6 +31,7 @@ use clap::{
 use directories::ProjectDirs;
 
 use layout::BBox;
+use remote::address::RemoteAddr;
 
 pub const BACKGROUND_COLOR: Color = Color::rgb(27.0 / 255.0, 32.0 / 255.0, 33.0 / 255.0); // eerie black 
 
@@ -68,6 +75,44 @@ struct CliArgs {
     /// Force the program to reset the settings to defaults on load.
     reset_to_default_settings: bool,
 
+    #[arg(long, value_name = "MILLIS")]
+    /// For testing: delay every message to and from the remote user. Overrides the configured net_sim settings.
+    sim_latency_ms: Option<u64>,
+
+    #[arg(long, value_name = "MILLIS")]
+    /// For testing: delay each message up to this much more or less than the simulated latency.
+    sim_jitter_ms: Option<u64>,
+
+    #[arg(long, value_parser=remote::netsim::parse_chance)]
+    /// For testing: the chance from 0 to 1 that a message to or from the remote user is dropped.
+    sim_loss: Option<f32>,
+
+    #[arg(long, value_parser=remote::netsim::parse_chance)]
+    /// For testing: the chance from 0 to 1 that a message is held back, and arrives after later ones.
+    sim_reorder: Option<f32>,
+
+    #[arg(long, value_parser=series::parse_best_of)]
+    /// Play a series of songs against the remote user instead of single songs, e.g. best of 3. Only with listen, connect or join.
+    /// Whoever wins the majority of rounds wins the series. You take turns picking the charts.
+    /// With listen and connect, the listening host picks first, and needs to give --best-of too.
+    /// In a relay room, only one of you gives --best-of, and that user picks first.
+    best_of: Option<u32>,
+
+    #[arg(long, value_name = "HALF", value_parser=coop::parse_lane_half)]
+    /// Play one chart together with the remote user instead of against them, on a shared panel.
+    /// Give `left` to play L1/L2 or `right` to play R1/R2, your partner takes the other half. You share one combined score.
+    /// Whoever plays the left half picks the charts.
+    coop: Option<coop::LaneHalf>,
+
+    #[arg(long, default_value_t = 2, value_parser=remote::relay::parse_player_count)]
+    /// How many users play in the match, counting yourself, from 2 up to 4.
+    /// Matches of more than two are played by joining a room on a relay, where everyone needs to give the same number.
+    players: usize,
+
+    #[arg(long, value_name = "FILE")]
+    /// Write every message to and from the remote user into this file, to be played back later with `replay`.
+    net_capture: Option<PathBuf>,
+
     #[command(subcommand)]
     /// What mode to run in
     mode: ConnectionMode,
@@ -83,20 +128,153 @@ enum ConnectionMode {
         /// Your remote partner will need this port and your IP address to connect.
         #[arg(long)]
         port: Option<u16>,
+
+        /// Encrypt the connection with a self-signed certificate, generated into the config directory.
+        /// Your remote partner will need to connect with wss:// or --tls.
+        #[arg(long)]
+        tls: bool,
+
+        /// Only let in a remote partner who knows this code. Overrides the configured settings pairing code.
+        #[arg(long, value_parser=remote::pairing::parse_pairing_code)]
+        pairing_code: Option<String>,
     },
     /// Connect to a remote host.
     Connect {
-        /// Attempts to connect to a remote IP address.
-        remote_addr: IpAddr,
+        /// Attempts to connect to a remote address. This may be an IP address, a host name,
+        /// either of those followed by `:port`, or a full ws:// or wss:// url.
+        /// IPv6 addresses need to be in brackets when followed by a port, e.g. `[::1]:8080`.
+        #[arg(value_parser=remote::address::parse_remote_addr)]
+        remote_addr: RemoteAddr,
+
+        /// Optionally, specify a port to connect to on the remote machine, if the address does not include one.
+        /// By default, this will take from the configured port in settings.toml
+        #[arg(long)]
+        remote_port: Option<u16>,
+
+        /// Connect with wss://, if the address does not give a scheme.
+        #[arg(long)]
+        tls: bool,
+
+        /// The code the host asked for. Overrides the configured settings pairing code.
+        #[arg(long, value_parser=remote::pairing::parse_pairing_code)]
+        pairing_code: Option<String>,
+    },
+    /// Join a room on a relay server, so that neither user needs to accept incoming connections.
+    Join {
+        /// The address of the relay server. Accepts the same forms as `connect`.
+        #[arg(value_parser=remote::address::parse_remote_addr)]
+        relay: RemoteAddr,
+
+        /// The room code to join. Your remote partner will need to join the same room.
+        #[arg(value_parser=remote::relay::parse_room_code)]
+        room: String,
+
+        /// Optionally, specify the port the relay is running on, if the address does not include one.
+        /// By default, this will take from the configured port in settings.toml
+        #[arg(long)]
+        relay_port: Option<u16>,
+    },
+    /// Watch a duel as a read-only spectator, from a listening host or a room on a relay server.
+    Spectate {
+        /// The address of the listening host, or of the relay server if a room is given. Accepts the same forms as `connect`.
+        #[arg(value_parser=remote::address::parse_remote_addr)]
+        remote_addr: RemoteAddr,
+
+        /// Supply this to watch a room on a relay server, instead of connecting to a listening host.
+        #[arg(long, value_parser=remote::relay::parse_room_code)]
+        room: Option<String>,
 
         /// Optionally, specify a port to connect to on the remote machine.
         /// By default, this will take from the configured port in settings.toml
         #[arg(long)]
         remote_port: Option<u16>,
+
+        /// Connect with wss://, if the address does not give a scheme. Only for watching a listening host.
+        #[arg(long)]
+        tls: bool,
+
+        /// The code the host asked for. Overrides the configured settings pairing code.
+        /// Rooms on a relay are not paired, so this is only for watching a listening host.
+        #[arg(long, value_parser=remote::pairing::parse_pairing_code)]
+        pairing_code: Option<String>,
     },
-    /// Record a song locally
+    /// Look for users listening on the local network, and print how to connect to them. Does not open a window.
+    Discover {
+        /// How many seconds to wait for hosts to announce themselves.
+        #[arg(long, default_value_t = 5)]
+        wait_secs: u64,
+    },
+    /// Run a headless relay server that pairs up users by room code. Does not open a window.
+    Relay {
+        /// Supply this parameter to override the configured settings default port.
+        #[arg(long)]
+        port: Option<u16>,
+    },
+    /// Play back a file written with --net-capture, watching both sides like a spectator. Does not connect to anyone.
+    Replay {
+        /// The capture file to play back.
+        capture: PathBuf,
+
+        /// How fast to play back the capture, e.g. 2.0 for double speed.
+        #[arg(long, default_value_t = 1.0, value_parser=remote::capture::parse_replay_speed)]
+        speed: f64,
+    },
+    /// Play against someone else at the same keyboard. The enemy panel is played with the second keymap in settings, J/K/L/; by default.
+    Local {
+    },
+    /// Play charts on your own, with nobody on the other panel.
+    Practice {
+    },
+    /// Record a new chart: tap the lanes along with a song, and each tap becomes a note.
+    /// The chart is saved to the chart directory when the song ends, or when you press the finish key.
     Record {
-        // TODO
+        #[command(flatten)]
+        args: record::RecordingArgs,
+    },
+    /// Write a draft chart for a song, with notes where new sounds start in it. Does not open a window.
+    /// The tempo and lanes are a starting point, to be touched up in the editor.
+    Generate {
+        #[command(flatten)]
+        args: generate::GenerateArgs,
+    },
+    /// Write a chart of made up patterns, like streams, trills, jacks and polyrhythms. Does not open a window.
+    Compose {
+        #[command(flatten)]
+        args: generate::ComposeArgs,
+    },
+    /// Check every chart for problems, like unknown keys, missing songs and jacks too fast to play.
+    /// Fails if any chart has errors. Does not open a window.
+    CheckCharts {
+        #[command(flatten)]
+        args: song::CheckArgs,
+    },
+    /// Upgrade charts in older formats to the current one, in place. The original of each is kept
+    /// next to it as a .bak file. Does not open a window.
+    MigrateCharts {
+        #[command(flatten)]
+        args: song::MigrateArgs,
+    },
+    /// Edit a chart with the mouse and keyboard, or start a new one. Notes sit still while you scroll through the chart.
+    /// The chart is only written to the chart directory when you save it.
+    Edit {
+        #[command(flatten)]
+        args: editor::EditArgs,
+    }
+}
+
+impl ConnectionMode {
+    /// Spectators only watch, they never play or send anything to the duelists.
+    /// Replays are watched the same way.
+    pub fn is_spectator(&self) -> bool {
+        matches!(self, ConnectionMode::Spectate { .. } | ConnectionMode::Replay { .. })
+    }
+    /// In a hot-seat match, the enemy panel is played on this keyboard instead of over the network.
+    pub fn is_hot_seat(&self) -> bool {
+        matches!(self, ConnectionMode::Local { .. })
+    }
+    /// Whether there is a remote user to play against, who sends their own scores
+    pub fn plays_remote_user(&self) -> bool {
+        matches!(self, ConnectionMode::Listen { .. } | ConnectionMode::Connect { .. } | ConnectionMode::Join { .. })
     }
 }
 
@@ -129,6 +307,47 @@ fn main() -> Result<()> {
 
     let settings = user_settings::load_settings(&cli)?;
 
+    // these modes run without the game
+    match &cli.mode {
+        ConnectionMode::Relay { port } => {
+            log::info!("Starting relay server...");
+            return remote::relay::run_relay(*port, &settings);
+        }
+        ConnectionMode::Discover { wait_secs } => {
+            return remote::discovery::run_discover(bevy::utils::Duration::from_secs(*wait_secs), &settings);
+        }
+        ConnectionMode::Generate { args } => {
+            return generate::run_generate(args);
+        }
+        ConnectionMode::Compose { args } => {
+            return generate::run_compose(args);
+        }
+        ConnectionMode::CheckCharts { args } => {
+            return song::run_check_charts(args);
+        }
+        ConnectionMode::MigrateCharts { args } => {
+            return song::run_migrate_charts(args);
+        }
+        _ => {}
+    }
+
+    // only the relay passes messages between more than two users, a listening host takes just the one
+    let many_players = matches!(
+        cli.mode,
+        ConnectionMode::Join { .. } | ConnectionMode::Spectate { room: Some(_), .. } | ConnectionMode::Replay { .. }
+    );
+    if cli.players > 2 && !many_players {
+        anyhow::bail!("--players {} needs everyone to join a room on a relay, only two can play with listen and connect", cli.players);
+    }
+
+    // each round of a series waits on the remote user's score
+    if cli.best_of.is_some() && !cli.mode.plays_remote_user() {
+        anyhow::bail!("--best-of needs a remote user to play against, with listen, connect or join");
+    }
+    if cli.best_of.is_some() && cli.coop.is_some() {
+        anyhow::bail!("--best-of can not be played in co-op, partners share one score");
+    }
+
     log::info!("Initializing app...");
 
 
@@ -150,6 +369,12 @@ fn main() -> Result<()> {
             selector_menu::ChartSelectorPlugin,
             remote::RemoteUserPlugin,
             record::RecordingPlugin,
+            attacks::AttacksPlugin,
+            series::SeriesPlugin,
+            coop::CoopPlugin,
+            chat::ChatPlugin,
+            editor::EditorPlugin,
+            metronome::MetronomePlugin,
         ))
 
         .config_if(cli.debug_inspector, |app| {
@@ -164,7 +389,13 @@ fn main() -> Result<()> {
         // Systems
         .add_systems(Startup, setup)
         .add_systems(OnEnter(layout::LayoutState::Done), make_window_visible)
-        .add_systems(Update, close_on_esc)
+        // escape throws away a chat message or a chart name instead, while typing one
+        .add_systems(Update, close_on_esc
+            .before(chat::type_chat_message)
+            .before(editor::type_save_as_name)
+            .run_if(chat::not_typing)
+            .run_if(editor::not_naming)
+        )
         .add_systems(Update, close_on_window_close_requested)
         .run();
     Ok(())
@@ -204,11 +435,17 @@ fn make_window_visible(mut window: Query<&mut Window>) {
 
 fn close_on_esc(
     input: Res<ButtonInput<KeyCode>>,
+    editor: Option<ResMut<editor::Editor>>,
     mut app_exit: ResMut<Events<bevy::app::AppExit>>,
 ) {
-    if input.just_pressed(KeyCode::Escape) {
-        teardown(app_exit.as_mut());
+    if !input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    // unsaved edits take a second press, so one stray escape does not throw them away
+    if editor.is_some_and(|mut editor| !editor.confirm_quit()) {
+        return;
     }
+    teardown(app_exit.as_mut());
 }
 
 fn close_on_window_close_requested(
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use bevy::audio::{
+    PlaybackMode,
+    Volume,
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+use crate::{
+    CliArgs,
+    ConnectionMode,
+};
+use crate::song::{
+    ArrowSpawner,
+    SongState,
+};
+use crate::team_markers::PlayerMarker;
+use crate::user_settings::UserSettings;
+
+const CLICK_SOUND: &str = "sounds/metronome-quartz.ogg";
+
+/// The first beat of a measure is played this much louder
+const ACCENT_VOLUME: f32 = 1.5;
+/// ...and this much higher, so it stands out even at full volume
+const ACCENT_SPEED: f32 = 1.5;
+
+/// A click on every beat of the song, while recording, editing or practising
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetronomeSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// From 0 (silent) to 1 (as loud as the click sound itself)
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// The first beat of every measure is accented
+    #[serde(default = "default_beats_per_measure")]
+    pub beats_per_measure: u32,
+}
+fn default_enabled() -> bool {
+    true
+}
+fn default_volume() -> f32 {
+    0.5
+}
+fn default_beats_per_measure() -> u32 {
+    4
+}
+impl Default for MetronomeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            volume: default_volume(),
+            beats_per_measure: default_beats_per_measure(),
+        }
+    }
+}
+
+/// Run condition for the metronome. Only modes without anyone else to play against have one.
+fn metronome_enabled(
+    cli: Res<CliArgs>,
+    settings: Res<UserSettings>,
+) -> bool {
+    let solo = matches!(cli.mode,
+        ConnectionMode::Record { .. }
+        | ConnectionMode::Edit { .. }
+        | ConnectionMode::Practice { }
+    );
+    solo && settings.metronome.enabled
+}
+
+/// Clicks whenever the spawner passes a beat of the song. The lead time before the first note clicks too, as a count in.
+fn click_on_beat(
+    mut commands: Commands,
+    settings: Res<UserSettings>,
+    asset_server: Res<AssetServer>,
+    spawner_q: Query<Ref<ArrowSpawner<PlayerMarker>>>,
+    mut last_beat: Local<Option<i64>>,
+) {
+    let Ok(spawner) = spawner_q.get_single() else {
+        return;
+    };
+
+    // the spawner counts chart rows, which may be several to a beat
+    let rows_per_beat = spawner.chart().rows_per_beat() as f32;
+    let beat = (spawner.curr_beat() / rows_per_beat).floor() as i64;
+
+    // every song counts its beats from the start
+    let passed = spawner.is_added() || last_beat.is_none_or(|last| beat > last);
+    // scrolling back does not click, but we need to catch the next beat after it
+    *last_beat = Some(beat);
+    if !passed {
+        return;
+    }
+
+    let metronome = &settings.metronome;
+    let accented = beat.rem_euclid(metronome.beats_per_measure.max(1) as i64) == 0;
+    let (volume, speed) = if accented {
+        (metronome.volume * ACCENT_VOLUME, ACCENT_SPEED)
+    } else {
+        (metronome.volume, 1.0)
+    };
+
+    commands.spawn(AudioBundle {
+        source: asset_server.load(CLICK_SOUND),
+        settings: PlaybackSettings {
+            mode: PlaybackMode::Despawn,
+            volume: Volume::new(volume),
+            speed,
+            ..default()
+        },
+    });
+}
+
+pub struct MetronomePlugin;
+impl Plugin for MetronomePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, click_on_beat
+                .run_if(metronome_enabled)
+                .run_if(in_state(SongState::Playing::<PlayerMarker>))
+            )
+        ;
+    }
+}
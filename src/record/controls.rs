@@ -6,7 +6,8 @@ use serde::{
 use crate::user_settings::UserSettings;
 use crate::keycode_serde;
 use crate::song::{
-    ArrowSpawner
+    ArrowSpawner,
+    SongState,
 };
 use crate::team_markers::{
     Marker,
@@ -23,7 +24,25 @@ pub struct RecordingKeymap {
     pub forward: KeyCode,
     /// Moves backward one beat.
     #[serde(with = "keycode_serde")]
-    pub backward: KeyCode
+    pub backward: KeyCode,
+    /// Ends the recording early and saves the chart.
+    #[serde(with = "keycode_serde", default = "default_finish_key")]
+    pub finish: KeyCode,
+    /// In the editor, plays the chart from the cursor, or stops playing it.
+    #[serde(with = "keycode_serde", default = "default_playtest_key")]
+    pub playtest: KeyCode,
+    /// While recording, tap along with the beat of the song to work out its tempo.
+    #[serde(with = "keycode_serde", default = "default_tap_tempo_key")]
+    pub tap_tempo: KeyCode,
+}
+fn default_finish_key() -> KeyCode {
+    KeyCode::Enter
+}
+fn default_playtest_key() -> KeyCode {
+    KeyCode::KeyP
+}
+fn default_tap_tempo_key() -> KeyCode {
+    KeyCode::KeyT
 }
 impl Default for RecordingKeymap {
     fn default() -> Self {
@@ -31,6 +50,9 @@ impl Default for RecordingKeymap {
             pause: KeyCode::Space,
             forward: KeyCode::ArrowDown,
             backward: KeyCode::ArrowUp,
+            finish: default_finish_key(),
+            playtest: default_playtest_key(),
+            tap_tempo: default_tap_tempo_key(),
         }
     }
 }
@@ -78,12 +100,32 @@ fn handle_scroll_actions<T: Marker>(
 
 }
 
+fn handle_finish_action(
+    settings: Res<UserSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut song_state: ResMut<NextState<SongState<PlayerMarker>>>,
+) {
+    let keymap = &settings.keybindings.recording_keymap;
+    if !keys.just_pressed(keymap.finish) {
+        return;
+    }
+    log::info!("finishing the recording early");
+    song_state.set(SongState::NotPlaying);
+}
+
 pub struct RecordingControlsPlugin;
 impl Plugin for RecordingControlsPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, handle_pause_actions::<PlayerMarker>)
-            .add_systems(Update, handle_scroll_actions::<PlayerMarker>)
+            // the audio keeps playing, so pausing or scrolling would put the recorded taps out of time with it
+            .add_systems(Update, (
+                handle_pause_actions::<PlayerMarker>,
+                handle_scroll_actions::<PlayerMarker>,
+            ).run_if(not(super::is_recording)))
+            .add_systems(Update, handle_finish_action
+                .run_if(super::is_recording)
+                .run_if(in_state(SongState::Playing::<PlayerMarker>))
+            )
         ;
     }
 }
@@ -1,13 +1,401 @@
+use std::path::Path;
+
+use anyhow::Result;
 use bevy::prelude::*;
 
 pub mod controls;
+pub mod quantize;
+pub mod tap_tempo;
+
+use crate::{
+    CliArgs,
+    ConnectionMode,
+};
+use crate::input::LaneHit;
+use crate::lane::Lane;
+use crate::song::{
+    ArrowSpawner,
+    Chart,
+    ChartAssets,
+    ChartName,
+    LoadChartRequest,
+    Note,
+    SongFinishedEvent,
+    SongState,
+};
+use crate::team_markers::PlayerMarker;
+use crate::user_settings::UserSettings;
+
+use quantize::{
+    Grid,
+    Quantizer,
+};
+use tap_tempo::TempoFit;
+
+/// Songs to record are looked up here, the same place charts look for them
+pub const SOUND_ASSET_PATH: &str = "assets/sounds/";
+
+/// How many beats the arrows take to scroll down while recording, and in the chart that gets written
+pub const LEAD_TIME_BEATS: f32 = 4.0;
+
+/// Without a length given, recording goes on until this long, or until the finish key is pressed
+const MAX_RECORDING_SECS: f32 = 600.0;
+
+/// The song has to be in the sounds folder, so that the chart can find it when it is played
+pub fn parse_sound_file(source: &str) -> Result<String> {
+    let file = source.trim();
+    let path = Path::new(SOUND_ASSET_PATH).join(file);
+    if !path.is_file() {
+        anyhow::bail!("no song at {}, songs need to be in {SOUND_ASSET_PATH}", path.display());
+    }
+    Ok(file.to_string())
+}
+
+pub fn parse_bpm(source: &str) -> Result<f32> {
+    let bpm: f32 = source.trim().parse()?;
+    if !bpm.is_finite() || bpm <= 0.0 {
+        anyhow::bail!("tempo must be a positive number of beats per minute, got {bpm}");
+    }
+    Ok(bpm)
+}
+
+/// What to record, as given on the command line
+#[derive(clap::Args)]
+#[derive(Debug, Clone)]
+pub struct RecordingArgs {
+    /// The song to chart, a file in the assets/sounds folder.
+    #[arg(value_parser=parse_sound_file)]
+    pub sound_file: String,
+
+    /// The tempo of the song, in beats per minute. Only a first guess if you tap the tempo while recording.
+    #[arg(value_parser=parse_bpm)]
+    pub bpm: f32,
+
+    /// What to call the new chart, without the .json. Defaults to the song's file name.
+    /// If there is already a chart by that name, a number is added on to the end.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// How many chart rows to a beat. Each tap is rounded to the nearest row.
+    #[arg(long, default_value_t = 4)]
+    pub subdivisions: u32,
+
+    /// How long to record for, in seconds. By default, recording goes on until you press the finish key.
+    #[arg(long)]
+    pub length_secs: Option<f32>,
+
+    /// Snap the taps to a grid before writing the chart: one of 1/1, 1/2, 1/3, 1/4, 1/6 or 1/8 of a beat.
+    /// By default, taps are only rounded to the nearest row.
+    #[arg(long, value_parser=quantize::parse_grid)]
+    pub quantize: Option<Grid>,
+
+    /// How far to pull each tap towards the grid, from 0 (not at all) to 1 (right onto the grid line).
+    /// Below 1, the chart gets four times as many rows, so the taps can land between the grid lines.
+    #[arg(long, default_value_t = 1.0, value_parser=quantize::parse_strength)]
+    pub strength: f32,
+
+    /// Report every note that quantizing moved by more than this many beats, so you can check on them.
+    /// The distance is to the row the note is written on.
+    #[arg(long, default_value_t = 0.1)]
+    pub report_threshold: f32,
+}
+
+/// How the chart lines up with the song
+#[derive(Debug, Copy, Clone)]
+pub struct Timing {
+    pub beat_duration_secs: f32,
+    /// How many beats of the song play before the first row of the chart arrives
+    pub lead_time_beats: f32,
+}
+impl Timing {
+    pub fn from_bpm(bpm: f32) -> Timing {
+        Timing {
+            beat_duration_secs: 60.0 / bpm,
+            lead_time_beats: LEAD_TIME_BEATS,
+        }
+    }
+    /// Puts the first row of the chart on a tapped beat
+    fn from_fit(fit: &TempoFit) -> Timing {
+        Timing::from_first_beat(fit.beat_duration_secs, fit.first_beat_secs)
+    }
+    /// Puts the first row of the chart on a beat of the song, given when any one beat plays.
+    /// The lead time grows by less than a beat to get there.
+    pub fn from_first_beat(beat_duration_secs: f32, first_beat_secs: f32) -> Timing {
+        let first_beat = first_beat_secs / beat_duration_secs;
+        Timing {
+            beat_duration_secs,
+            lead_time_beats: LEAD_TIME_BEATS + (first_beat - LEAD_TIME_BEATS).rem_euclid(1.0),
+        }
+    }
+    pub fn bpm(&self) -> f32 {
+        60.0 / self.beat_duration_secs
+    }
+    /// Seconds into the song that a beat of the chart plays
+    fn secs_at(&self, beat: f32) -> f32 {
+        (beat + self.lead_time_beats) * self.beat_duration_secs
+    }
+    /// The beat of the chart that plays this many seconds into the song
+    pub fn beat_at(&self, secs: f32) -> f32 {
+        secs / self.beat_duration_secs - self.lead_time_beats
+    }
+}
+
+/// A recording in progress. Each lane hit while the song plays becomes a note.
+#[derive(Resource)]
+#[derive(Debug)]
+pub struct Recording {
+    chart_name: ChartName,
+    sound_file: String,
+    bpm: f32,
+    /// How many chart rows to a beat while recording
+    subdivisions: u32,
+    /// Snaps the taps before writing the chart, if we were asked to
+    quantizer: Option<Quantizer>,
+    /// Quantized notes that moved more than this many beats get reported
+    report_threshold: f32,
+    /// Every lane hit so far, at the chart row it was hit on. Not rounded yet
+    taps: Vec<(Lane, f32)>,
+    /// Every press of the tap tempo key so far, in seconds from the start of the song
+    tempo_taps: Vec<f32>,
+    /// Set once the song starts playing, so that we only save after we actually recorded
+    in_progress: bool,
+    /// Set once the chart is written, after which playing it back does not record over it
+    saved: bool,
+}
+impl Recording {
+    /// The timing the song is played back with while recording, from the tempo on the command line
+    fn recorded_timing(&self) -> Timing {
+        Timing::from_bpm(self.bpm)
+    }
+    /// The tapped tempo, once there are enough taps to tell
+    fn tempo_fit(&self) -> Option<TempoFit> {
+        tap_tempo::fit_tempo(self.tempo_taps.as_slice())
+    }
+    /// A chart with no notes, just long enough to record over
+    fn empty_chart(&self, length_secs: f32) -> Chart {
+        let timing = self.recorded_timing();
+        let rows = (length_secs / timing.beat_duration_secs * self.subdivisions as f32).ceil() as usize;
+        self.chart_with(timing, self.subdivisions, vec![Vec::new(); rows])
+    }
+    /// The lead time is always given in beats, so the song lines up with the notes at any number of rows to a beat
+    fn chart_with(&self, timing: Timing, rows_per_beat: u32, beats: Vec<Vec<Note>>) -> Chart {
+        Chart::new(
+            self.chart_name.clone(),
+            Some(format!("Recorded to {} at {:.2} bpm", self.sound_file, timing.bpm())),
+            timing.beat_duration_secs / rows_per_beat as f32,
+            timing.lead_time_beats * rows_per_beat as f32,
+            Some(self.sound_file.clone()),
+            beats,
+        ).with_rows_per_beat(rows_per_beat)
+    }
+    /// The taps in beats of the finished chart, snapped to the grid if we are quantizing,
+    /// along with how many rows to a beat the chart needs
+    fn quantized_taps(&self, timing: Timing) -> (u32, Vec<(Lane, f32)>) {
+        let recorded = self.recorded_timing();
+        let subdivisions = self.subdivisions as f32;
+        let taps: Vec<(Lane, f32)> = self.taps
+            .iter()
+            .map(|&(lane, row)| (lane, timing.beat_at(recorded.secs_at(row / subdivisions))))
+            .collect();
+
+        let Some(quantizer) = self.quantizer.as_ref() else {
+            return (self.subdivisions, taps);
+        };
+
+        let rows_per_beat = quantize::rows_per_beat(self.subdivisions, quantizer);
+        let (taps, moved) = quantize::quantize_taps(quantizer, taps.as_slice(), rows_per_beat, self.report_threshold);
+        log::info!("quantized {} notes to a {} grid at strength {}, {rows_per_beat} rows to a beat", taps.len(), quantizer.grid, quantizer.strength);
+        if !moved.is_empty() {
+            log::warn!("{} notes moved more than {} beats, you may want to check on them:", moved.len(), self.report_threshold);
+        }
+        for note in moved.iter() {
+            log::warn!("  {} at beat {:.3} moved to {:.3}, {:.3} beats", note.lane.as_str(), note.from_beat, note.to_beat, note.distance());
+        }
+
+        (rows_per_beat, taps)
+    }
+    /// Each tap becomes a note on its nearest row
+    fn to_chart(&self) -> Chart {
+        let timing = match self.tempo_fit() {
+            Some(fit) => {
+                log::info!("tapped tempo is {:.2} bpm, with the first beat {:.3}s into the song, from {} of {} taps",
+                    fit.bpm(), fit.first_beat_secs, fit.taps_used, self.tempo_taps.len());
+                Timing::from_fit(&fit)
+            }
+            None => {
+                if !self.tempo_taps.is_empty() {
+                    log::warn!("not enough steady taps to tell the tempo, keeping {} bpm", self.bpm);
+                }
+                self.recorded_timing()
+            }
+        };
+        let (rows_per_beat, taps) = self.quantized_taps(timing);
+        let mut beats: Vec<Vec<Note>> = Vec::new();
+
+        for (lane, beat) in taps {
+            let row = (beat * rows_per_beat as f32).round();
+            if row < 0.0 {
+                log::warn!("dropping {} tap before the first beat of the chart", lane.as_str());
+                continue;
+            }
+            let row = row as usize;
+            if beats.len() <= row {
+                beats.resize(row + 1, Vec::new());
+            }
+            // double taps on the same row would be one note anyway
+            if beats[row].iter().any(|note| note.lane() == lane) {
+                continue;
+            }
+            beats[row].push(Note::new(lane));
+        }
+
+        self.chart_with(timing, rows_per_beat, beats)
+    }
+}
+
+/// Chart names go in a file name, so we pick the first free one based on the song's name
+pub fn free_chart_name(base: &str) -> ChartName {
+    let name = ChartName::new(base);
+    if !name.is_taken() {
+        return name;
+    }
+    (2..)
+        .map(|i| ChartName::new(format!("{base}-{i}")))
+        .find(|name| !name.is_taken())
+        .expect("some chart name is free")
+}
+
+fn start_recording(
+    mut commands: Commands,
+    cli: Res<CliArgs>,
+    mut chart_assets: ResMut<ChartAssets>,
+    mut load_chart_ev: EventWriter<LoadChartRequest<PlayerMarker>>,
+) {
+    let ConnectionMode::Record { args } = &cli.mode else {
+        return;
+    };
+
+    let base_name = args.name.clone().unwrap_or_else(|| {
+        Path::new(args.sound_file.as_str())
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("recording")
+            .to_string()
+    });
+
+    let recording = Recording {
+        chart_name: free_chart_name(base_name.as_str()),
+        sound_file: args.sound_file.clone(),
+        bpm: args.bpm,
+        subdivisions: args.subdivisions.max(1),
+        quantizer: args.quantize.map(|grid| Quantizer {
+            grid,
+            strength: args.strength,
+        }),
+        report_threshold: args.report_threshold,
+        taps: Vec::new(),
+        tempo_taps: Vec::new(),
+        in_progress: false,
+        saved: false,
+    };
+    log::info!("recording {} at {} bpm into chart {}", recording.sound_file, recording.bpm, recording.chart_name);
+
+    let chart = recording.empty_chart(args.length_secs.unwrap_or(MAX_RECORDING_SECS));
+    chart_assets.insert(chart);
+    load_chart_ev.send(LoadChartRequest::from(recording.chart_name.clone()));
+
+    commands.insert_resource(recording);
+}
+
+fn mark_recording_in_progress(
+    mut recording: ResMut<Recording>,
+    spawner_q: Query<&ArrowSpawner<PlayerMarker>>,
+) {
+    recording.in_progress = !recording.saved && spawner_q
+        .get_single()
+        .is_ok_and(|spawner| spawner.chart().chart_name() == &recording.chart_name);
+}
+
+fn record_lane_hits(
+    mut recording: ResMut<Recording>,
+    mut lane_hit_ev: EventReader<LaneHit>,
+) {
+    if !recording.in_progress {
+        lane_hit_ev.clear();
+        return;
+    }
+    for ev in lane_hit_ev.read() {
+        recording.taps.push((ev.lane(), ev.beat()));
+    }
+}
+
+/// The tap tempo key marks where the author hears each beat, to work out the tempo from
+fn record_tempo_taps(
+    time: Res<Time>,
+    settings: Res<UserSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    spawner_q: Query<&ArrowSpawner<PlayerMarker>>,
+    mut recording: ResMut<Recording>,
+) {
+    if !recording.in_progress || !keys.just_pressed(settings.keybindings.recording_keymap.tap_tempo) {
+        return;
+    }
+    let Ok(spawner) = spawner_q.get_single() else {
+        return;
+    };
+
+    let secs = time.elapsed().as_secs_f32() - spawner.song_start();
+    recording.tempo_taps.push(secs);
+
+    if let Some(fit) = recording.tempo_fit() {
+        log::info!("tapped tempo so far: {:.2} bpm", fit.bpm());
+    }
+}
+
+/// Writes the new chart once the song is over, and makes it available to play right away
+fn save_recording_on_song_end(
+    mut recording: ResMut<Recording>,
+    mut chart_assets: ResMut<ChartAssets>,
+    mut song_end_ev: EventReader<SongFinishedEvent<PlayerMarker>>,
+) {
+    if song_end_ev.is_empty() {
+        return;
+    }
+    song_end_ev.clear();
+
+    if !recording.in_progress {
+        return; // the song ended without us recording, i.e. on start up
+    }
+    recording.in_progress = false;
+    recording.saved = true;
+
+    let chart = recording.to_chart();
+    match chart.save_new() {
+        Ok(path) => log::info!("recorded {} notes into {}", recording.taps.len(), path.display()),
+        Err(e) => log::error!("unable to save recording: {e:?}"),
+    }
+    chart_assets.insert(chart);
+}
+
+/// Run condition for recording
+pub fn is_recording(recording: Option<Res<Recording>>) -> bool {
+    recording.is_some()
+}
 
 pub struct RecordingPlugin;
 impl Plugin for RecordingPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_systems(Startup, start_recording)
+            .add_systems(OnEnter(SongState::Playing::<PlayerMarker>), mark_recording_in_progress.run_if(is_recording))
+            .add_systems(Update, (
+                    record_lane_hits,
+                    record_tempo_taps,
+                    save_recording_on_song_end
+                        .after(record_lane_hits)
+                        .after(record_tempo_taps),
+            ).run_if(is_recording))
             .add_plugins(controls::RecordingControlsPlugin)
         ;
     }
 }
-
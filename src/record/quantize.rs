@@ -0,0 +1,190 @@
+use anyhow::Result;
+
+use crate::lane::Lane;
+
+/// The grids a recording can be snapped to, as divisions of a beat
+const GRID_DIVISIONS: [u32; 6] = [1, 2, 3, 4, 6, 8];
+
+/// Taps pulled only part of the way to the grid land between its lines, so the chart gets this many
+/// rows for each row it would have had. Otherwise rounding to a row would undo most of the pull
+const PARTIAL_STRENGTH_ROWS: u32 = 4;
+
+/// Lines at every `1/divisions` of a beat
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Grid {
+    divisions: u32,
+}
+impl Grid {
+    /// The grid line closest to the beat
+    fn nearest_line(self, beat: f32) -> f32 {
+        let divisions = self.divisions as f32;
+        (beat * divisions).round() / divisions
+    }
+}
+impl std::fmt::Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "1/{}", self.divisions)
+    }
+}
+
+/// Accepts `1/4` or just `4`
+pub fn parse_grid(source: &str) -> Result<Grid> {
+    let source = source.trim();
+    let divisions = source.strip_prefix("1/").unwrap_or(source);
+    let divisions: u32 = divisions.parse()?;
+    if !GRID_DIVISIONS.contains(&divisions) {
+        anyhow::bail!("grid must be one of 1/1, 1/2, 1/3, 1/4, 1/6 or 1/8, got {source}");
+    }
+    Ok(Grid { divisions })
+}
+
+pub fn parse_strength(source: &str) -> Result<f32> {
+    let strength: f32 = source.trim().parse()?;
+    if !(0.0..=1.0).contains(&strength) {
+        anyhow::bail!("strength must be between 0 and 1, got {strength}");
+    }
+    Ok(strength)
+}
+
+/// Pulls recorded taps towards the grid. At full strength every tap lands on a grid line,
+/// at half strength each tap moves halfway there, and so on.
+#[derive(Debug, Copy, Clone)]
+pub struct Quantizer {
+    pub grid: Grid,
+    pub strength: f32,
+}
+impl Quantizer {
+    pub fn quantize(&self, beat: f32) -> f32 {
+        let target = self.grid.nearest_line(beat);
+        beat + (target - beat) * self.strength
+    }
+    fn is_partial(&self) -> bool {
+        self.strength < 1.0
+    }
+}
+
+/// A tap that the quantizer moved further than the author might expect
+#[derive(Debug, Clone)]
+pub struct MovedNote {
+    pub lane: Lane,
+    pub from_beat: f32,
+    pub to_beat: f32,
+}
+impl MovedNote {
+    pub fn distance(&self) -> f32 {
+        (self.to_beat - self.from_beat).abs()
+    }
+}
+
+/// Quantizes every tap, given in beats, and rounds it to the nearest of `rows_per_beat` rows.
+/// Keeps track of the ones that ended up more than `threshold` beats from where they were tapped
+pub fn quantize_taps(quantizer: &Quantizer, taps: &[(Lane, f32)], rows_per_beat: u32, threshold: f32) -> (Vec<(Lane, f32)>, Vec<MovedNote>) {
+    let rows_per_beat = rows_per_beat as f32;
+    let mut moved = Vec::new();
+
+    let quantized = taps
+        .iter()
+        .map(|&(lane, from_beat)| {
+            let to_beat = (quantizer.quantize(from_beat) * rows_per_beat).round() / rows_per_beat;
+            let note = MovedNote { lane, from_beat, to_beat };
+            if note.distance() > threshold {
+                moved.push(note);
+            }
+            (lane, to_beat)
+        })
+        .collect();
+
+    (quantized, moved)
+}
+
+/// Chart rows have to fall on both the recording's rows and the grid's lines,
+/// and in between them too when the taps are only pulled part of the way
+pub fn rows_per_beat(subdivisions: u32, quantizer: &Quantizer) -> u32 {
+    fn gcd(a: u32, b: u32) -> u32 {
+        if b == 0 { a } else { gcd(b, a % b) }
+    }
+    let divisions = quantizer.grid.divisions;
+    let rows = subdivisions / gcd(subdivisions, divisions) * divisions;
+    if quantizer.is_partial() {
+        rows * PARTIAL_STRENGTH_ROWS
+    } else {
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantizer(grid: &str, strength: f32) -> Quantizer {
+        Quantizer {
+            grid: parse_grid(grid).unwrap(),
+            strength,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn rows_fall_on_both_the_recording_and_the_grid() {
+        assert_eq!(rows_per_beat(4, &quantizer("1/3", 1.0)), 12);
+        assert_eq!(rows_per_beat(4, &quantizer("1/4", 1.0)), 4);
+        assert_eq!(rows_per_beat(4, &quantizer("1/8", 1.0)), 8);
+        assert_eq!(rows_per_beat(6, &quantizer("1/4", 1.0)), 12);
+    }
+
+    #[test]
+    fn partial_strength_gets_finer_rows() {
+        assert_eq!(rows_per_beat(4, &quantizer("1/3", 0.5)), 12 * PARTIAL_STRENGTH_ROWS);
+    }
+
+    #[test]
+    fn full_strength_snaps_to_a_third_grid_on_four_subdivisions() {
+        let quantizer = quantizer("1/3", 1.0);
+        let rows = rows_per_beat(4, &quantizer);
+        let taps = [(Lane::L1, 1.30), (Lane::R1, 0.5), (Lane::L2, 2.0)];
+
+        let (quantized, moved) = quantize_taps(&quantizer, &taps, rows, 0.1);
+
+        assert_close(quantized[0].1, 4.0 / 3.0);
+        assert_close(quantized[1].1, 2.0 / 3.0);
+        assert_close(quantized[2].1, 2.0);
+        // every note lands on a whole row
+        for &(_, beat) in quantized.iter() {
+            assert_close((beat * rows as f32).round(), beat * rows as f32);
+        }
+        // only the tap halfway between two lines moved far
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].lane, Lane::R1);
+        assert_close(moved[0].distance(), 2.0 / 3.0 - 0.5);
+    }
+
+    #[test]
+    fn partial_strength_keeps_its_pull_after_rounding() {
+        let quantizer = quantizer("1/3", 0.5);
+        let rows = rows_per_beat(4, &quantizer);
+        let taps = [(Lane::L1, 1.30)];
+
+        let (quantized, moved) = quantize_taps(&quantizer, &taps, rows, 0.0);
+
+        // pulled halfway to 4/3 is 1.3167, and the nearest of 48 rows to a beat is 63/48
+        assert_close(quantized[0].1, 63.0 / 48.0);
+        assert!(quantized[0].1 > 1.30 && quantized[0].1 < 4.0 / 3.0);
+        // the reported distance is to where the note is written, not to where it was pulled
+        assert_close(moved[0].distance(), 63.0 / 48.0 - 1.30);
+    }
+
+    #[test]
+    fn zero_strength_leaves_taps_on_their_nearest_row() {
+        let quantizer = quantizer("1/4", 0.0);
+        let rows = rows_per_beat(4, &quantizer);
+        let taps = [(Lane::R2, 0.61)];
+
+        let (quantized, moved) = quantize_taps(&quantizer, &taps, rows, 0.05);
+
+        assert_close(quantized[0].1, 10.0 / 16.0);
+        assert!(moved.is_empty());
+    }
+}
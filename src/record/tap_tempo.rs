@@ -0,0 +1,174 @@
+/// Fewest taps we will guess a tempo from
+pub const MIN_TAPS: usize = 4;
+
+/// Taps closer together than this are a double press, not two beats
+const MIN_INTERVAL_SECS: f32 = 0.1;
+
+/// How many times to guess which beat each tap was on, then fit the line again
+const FIT_ROUNDS: usize = 3;
+
+/// Taps further than this fraction of a beat off the fitted tempo are left out of the final fit
+const OUTLIER_BEATS: f32 = 0.25;
+
+/// The tempo the author tapped along to
+#[derive(Debug, Copy, Clone)]
+pub struct TempoFit {
+    pub beat_duration_secs: f32,
+    /// When the first beat of the song plays, in seconds from the start of the song
+    pub first_beat_secs: f32,
+    /// How many of the taps fit the tempo, the rest were left out as mistakes
+    pub taps_used: usize,
+}
+impl TempoFit {
+    pub fn bpm(&self) -> f32 {
+        60.0 / self.beat_duration_secs
+    }
+}
+
+fn median(mut values: Vec<f32>) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f32::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Fits `secs = offset + beat * beat_duration` through (beat, secs) points, using the median of the slopes
+/// between every pair of points, so that a few bad taps can not pull the line off
+fn theil_sen(points: &[(f32, f32)]) -> Option<(f32, f32)> {
+    let slopes = points
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &(beat_a, secs_a))| {
+            points[i + 1..]
+                .iter()
+                .filter(move |&&(beat_b, _)| beat_b != beat_a)
+                .map(move |&(beat_b, secs_b)| (secs_b - secs_a) / (beat_b - beat_a))
+        })
+        .collect();
+    let beat_duration = median(slopes)?;
+    let offset = median(points.iter().map(|&(beat, secs)| secs - beat * beat_duration).collect())?;
+    Some((beat_duration, offset))
+}
+
+/// Which beat each tap was meant to land on, given a guess at the tempo
+fn assign_beats(taps: &[f32], beat_duration: f32, offset: f32) -> Vec<(f32, f32)> {
+    taps
+        .iter()
+        .map(|&secs| (((secs - offset) / beat_duration).round(), secs))
+        .collect()
+}
+
+/// Guesses the tempo and where the beats fall from taps along with the song, given in seconds from its start.
+/// Taps may skip beats, and a few may be off entirely.
+pub fn fit_tempo(taps: &[f32]) -> Option<TempoFit> {
+    if taps.len() < MIN_TAPS {
+        return None;
+    }
+    let mut taps = taps.to_vec();
+    taps.sort_by(f32::total_cmp);
+
+    // most taps are one beat apart, so the typical gap is a good first guess
+    let intervals = taps
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|&interval| interval > MIN_INTERVAL_SECS)
+        .collect();
+    let mut beat_duration = median(intervals)?;
+    let mut offset = taps[0];
+
+    for _ in 0..FIT_ROUNDS {
+        let points = assign_beats(taps.as_slice(), beat_duration, offset);
+        (beat_duration, offset) = theil_sen(points.as_slice())?;
+        if !beat_duration.is_finite() || beat_duration <= MIN_INTERVAL_SECS {
+            return None;
+        }
+    }
+
+    let inliers: Vec<(f32, f32)> = assign_beats(taps.as_slice(), beat_duration, offset)
+        .into_iter()
+        .filter(|&(beat, secs)| (secs - (offset + beat * beat_duration)).abs() <= OUTLIER_BEATS * beat_duration)
+        .collect();
+    if inliers.len() < MIN_TAPS {
+        return None;
+    }
+    let (beat_duration, offset) = theil_sen(inliers.as_slice())?;
+    if !beat_duration.is_finite() || beat_duration <= MIN_INTERVAL_SECS {
+        return None;
+    }
+
+    Some(TempoFit {
+        beat_duration_secs: beat_duration,
+        first_beat_secs: offset.rem_euclid(beat_duration),
+        taps_used: inliers.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEAT_SECS: f32 = 0.5;
+    const FIRST_BEAT_SECS: f32 = 0.2;
+
+    fn tap_on(beat: u32) -> f32 {
+        FIRST_BEAT_SECS + beat as f32 * BEAT_SECS
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.005, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn fits_steady_taps_with_a_skipped_beat_and_an_outlier() {
+        let mut taps: Vec<f32> = (0..12)
+            .filter(|&beat| beat != 5)
+            .map(tap_on)
+            .collect();
+        // far enough off its beat to be a mistake
+        taps[7] += 0.4 * BEAT_SECS;
+
+        let fit = fit_tempo(taps.as_slice()).expect("a tempo");
+        assert_close(fit.beat_duration_secs, BEAT_SECS);
+        assert_close(fit.first_beat_secs, FIRST_BEAT_SECS);
+        assert!((fit.bpm() - 120.0).abs() < 1.0, "expected 120 bpm, got {}", fit.bpm());
+        assert_eq!(fit.taps_used, 10);
+    }
+
+    #[test]
+    fn double_presses_do_not_halve_the_tempo() {
+        let mut taps: Vec<f32> = (0..8).map(tap_on).collect();
+        taps.push(tap_on(3) + 0.03);
+        taps.push(tap_on(6) + 0.02);
+
+        let fit = fit_tempo(taps.as_slice()).expect("a tempo");
+        assert_close(fit.beat_duration_secs, BEAT_SECS);
+        assert_close(fit.first_beat_secs, FIRST_BEAT_SECS);
+    }
+
+    #[test]
+    fn taps_out_of_order_fit_the_same() {
+        let mut taps: Vec<f32> = (0..8).map(tap_on).collect();
+        taps.reverse();
+
+        let fit = fit_tempo(taps.as_slice()).expect("a tempo");
+        assert_close(fit.beat_duration_secs, BEAT_SECS);
+        assert_eq!(fit.taps_used, 8);
+    }
+
+    #[test]
+    fn too_few_taps_have_no_tempo() {
+        let taps: Vec<f32> = (0..MIN_TAPS as u32 - 1).map(tap_on).collect();
+        assert!(fit_tempo(taps.as_slice()).is_none());
+    }
+
+    #[test]
+    fn taps_all_at_once_have_no_tempo() {
+        assert!(fit_tempo(&[1.0, 1.01, 1.02, 1.03, 1.04]).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use std::net::IpAddr;
+
+use anyhow::{
+    Result,
+    Context,
+    anyhow,
+};
+use url::{
+    Host,
+    Url,
+};
+
+/// Where to find a remote machine, as given on the command line.
+/// Either a full `ws://` or `wss://` url, or a `host[:port]` where the host is an IP address or DNS name.
+#[derive(Debug, Clone)]
+pub struct RemoteAddr {
+    /// Scheme, host and path of the remote. The port is kept separately
+    url: Url,
+    /// The port that was asked for, or `None` to fall back to the configured port
+    port: Option<u16>,
+    /// Whether the scheme was spelled out, or is up to whether we use TLS
+    explicit_scheme: bool,
+}
+impl RemoteAddr {
+    fn from_host(host: Host, port: Option<u16>) -> Result<RemoteAddr> {
+        // formatting the host puts the brackets around IPv6 addresses for us
+        let url = Url::parse(format!("ws://{host}/").as_str())
+            .with_context(|| format!("unable to make url from host {host}"))?;
+        Ok(RemoteAddr { url, port, explicit_scheme: false })
+    }
+
+    /// Builds the url to connect to, adding `segments` on to the end of the path.
+    /// A port given in the address takes priority over `fallback_port`,
+    /// and a scheme given in the address takes priority over `fallback_tls`.
+    pub fn to_url(&self, fallback_port: u16, fallback_tls: bool, segments: &[&str]) -> Result<Url> {
+        let mut url = self.url.clone();
+
+        if fallback_tls && !self.explicit_scheme {
+            url.set_scheme("wss")
+                .map_err(|_| anyhow!("unable to use wss:// for {url}"))?;
+        }
+
+        let port = self.port.unwrap_or(fallback_port);
+        url.set_port(Some(port))
+            .map_err(|_| anyhow!("unable to set port {port} on {url}"))?;
+
+        if !segments.is_empty() {
+            let base = url.to_string();
+            url.path_segments_mut()
+                .map_err(|_| anyhow!("unable to add a path to {base}"))?
+                .pop_if_empty()
+                .extend(segments);
+        }
+
+        Ok(url)
+    }
+}
+
+impl std::fmt::Display for RemoteAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}", self.url.scheme(), self.url.host_str().unwrap_or(""))?;
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        match self.url.path() {
+            "/" => Ok(()),
+            path => write!(f, "{path}"),
+        }
+    }
+}
+
+/// Whether the authority of a url, e.g. `[::1]:80` in `ws://[::1]:80/duel`, spells out a port
+fn has_port(url: &str) -> bool {
+    let authority = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest);
+    let authority = authority
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    // anything before an @ is a user name and password, which may have a colon of their own
+    let authority = authority
+        .rsplit('@')
+        .next()
+        .unwrap_or_default();
+    match authority.rfind(':') {
+        Some(i) => !authority[i..].contains(']'),
+        None => false,
+    }
+}
+
+pub fn parse_remote_addr(source: &str) -> Result<RemoteAddr> {
+    let source = source.trim();
+
+    // a full url, e.g. wss://example.com:9000/duel
+    if source.contains("://") {
+        let url = Url::parse(source)
+            .with_context(|| format!("unable to parse url: {source}"))?;
+
+        match url.scheme() {
+            "ws" | "wss" => {}
+            scheme => anyhow::bail!("unsupported scheme {scheme}://, expected ws:// or wss://"),
+        }
+        if url.host().is_none() {
+            anyhow::bail!("url has no host: {source}");
+        }
+
+        // no port falls back to --remote-port or the configured port, like a bare host does.
+        // The url crate forgets a port that is the default for its scheme, e.g. ws://host:80, so look for one in the text too
+        let port = url.port().or_else(|| has_port(source).then(|| url.port_or_known_default()).flatten());
+        return Ok(RemoteAddr { url, port, explicit_scheme: true });
+    }
+
+    // bare IPv6 addresses look like they have a port, so we have to check for them first
+    if let Ok(ip) = source.parse::<IpAddr>() {
+        let host = match ip {
+            IpAddr::V4(ip) => Host::Ipv4(ip),
+            IpAddr::V6(ip) => Host::Ipv6(ip),
+        };
+        return RemoteAddr::from_host(host, None);
+    }
+
+    // host[:port], where IPv6 hosts need to be in brackets, e.g. [::1]:8080
+    let (host, port) = match source.rfind(':') {
+        Some(i) if !source[i..].contains(']') => (&source[..i], Some(&source[i + 1..])),
+        _ => (source, None),
+    };
+
+    let host = Host::parse(host)
+        .with_context(|| format!("not a valid host name or IP address: {host}"))?;
+
+    let port = port
+        .map(|port| port.parse::<u16>())
+        .transpose()
+        .with_context(|| format!("not a valid port in {source}"))?;
+
+    RemoteAddr::from_host(host, port)
+}
@@ -0,0 +1,138 @@
+use std::{
+    fs::File,
+    io::{
+        BufRead,
+        BufReader,
+        LineWriter,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Instant,
+};
+
+use anyhow::{
+    Result,
+    Context,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::team_markers::Team;
+
+use super::GameMessage;
+
+/// Which way a captured message was going
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// From the remote user to us
+    Incoming,
+    /// From us to the remote user
+    Outgoing,
+}
+
+/// One line of a capture file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    /// Seconds since the capture started, used to pace the replay
+    elapsed_secs: f64,
+    /// Wall clock time, for lining the capture up with the logs
+    local_time: String,
+    direction: Direction,
+    message: GameMessage,
+}
+impl CaptureEntry {
+    pub fn elapsed_secs(&self) -> f64 {
+        self.elapsed_secs
+    }
+    /// The message as it should be replayed.
+    /// Replays are watched like a spectator, so our own messages go on the player panel.
+    pub fn into_replayed(self) -> GameMessage {
+        match self.direction {
+            Direction::Incoming => self.message,
+            Direction::Outgoing => GameMessage::Spectated {
+                seat: Team::Player,
+                message: Box::new(self.message),
+            },
+        }
+    }
+}
+
+/// Writes every message to and from the remote user into a file, one JSON object per line
+#[derive(Debug)]
+pub struct CaptureWriter {
+    file: LineWriter<File>,
+    path: PathBuf,
+    started: Instant,
+}
+impl CaptureWriter {
+    pub fn create(path: &Path) -> Result<CaptureWriter> {
+        let file = File::create(path)
+            .with_context(|| format!("creating net capture at {}", path.display()))?;
+
+        log::info!("capturing network traffic to {}", path.display());
+
+        Ok(CaptureWriter {
+            file: LineWriter::new(file),
+            path: path.to_path_buf(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Writes the message to the capture. Failures are only logged, they should never stop the game.
+    pub fn record(&mut self, direction: Direction, message: &GameMessage) {
+        let entry = CaptureEntry {
+            elapsed_secs: self.started.elapsed().as_secs_f64(),
+            local_time: chrono::Local::now().to_rfc3339(),
+            direction,
+            message: message.clone(),
+        };
+
+        let Ok(line) = serde_json::to_string(&entry)
+            .inspect_err(|e| log::error!("unable to serialize captured message: {e}"))
+            else { return; };
+
+        if let Err(e) = writeln!(self.file, "{line}") {
+            log::error!("unable to write to net capture at {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Reads back a file written by `CaptureWriter`, in the order the messages were captured
+pub fn read_capture(path: &Path) -> Result<Vec<CaptureEntry>> {
+    let file = File::open(path)
+        .with_context(|| format!("opening net capture at {}", path.display()))?;
+
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line
+            .with_context(|| format!("reading net capture at {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry: CaptureEntry = serde_json::from_str(line.as_str())
+            .with_context(|| format!("parsing line {} of net capture at {}", i + 1, path.display()))?;
+        // negative, NaN or absurdly far off times can not be waited for, and only come from an edited or broken file
+        if std::time::Duration::try_from_secs_f64(entry.elapsed_secs).is_err() {
+            anyhow::bail!("line {} of net capture at {} has an elapsed time of {}s, which can not be replayed", i + 1, path.display(), entry.elapsed_secs);
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Replay speeds multiply how fast the capture plays back
+pub fn parse_replay_speed(source: &str) -> Result<f64> {
+    let speed: f64 = source.trim().parse()
+        .with_context(|| format!("{source} is not a number"))?;
+    if speed.is_nan() || speed <= 0.0 {
+        anyhow::bail!("replay speed must be more than 0, got {speed}");
+    }
+    Ok(speed)
+}
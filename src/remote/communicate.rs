@@ -5,7 +5,8 @@ use std::net::{
 };
 use anyhow::{
     Result,
-    Context
+    Context,
+    anyhow,
 };
 use futures_util::{
     SinkExt,
@@ -16,11 +17,25 @@ use futures_util::{
 use tokio::{
     net::{
         TcpListener,
+        TcpStream,
+        lookup_host,
     },
-    sync::mpsc,
+    sync::{
+        mpsc,
+        broadcast,
+    },
+};
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::handshake::server::{
+    Request,
+    Response,
+    ErrorResponse,
 };
 
-use url::Url;
+use url::{
+    Host,
+    Url,
+};
 
 use crate::{
     ConnectionMode,
@@ -28,8 +43,26 @@ use crate::{
     user_settings::UserSettings,
 };
 
+use crate::team_markers::Team;
+
 use super::{
     GameMessage,
+    SPECTATE_PATH,
+    capture::{
+        self,
+        CaptureEntry,
+        CaptureWriter,
+        Direction,
+    },
+    discovery,
+    netsim,
+    pairing,
+    tls::{
+        self,
+        BoxedTransport,
+        Fingerprint,
+        HostTls,
+    },
     widgets::NetStatus,
 };
 
@@ -46,6 +79,9 @@ pub struct Comms {
     
     net_status: NetStatus,
 
+    /// Set with --net-capture, records every message to and from the remote user
+    capture: Option<CaptureWriter>,
+
     /// Keep the tokio runtime around that is computing our background tasks.
     _runtime: tokio::runtime::Runtime,
 }
@@ -53,6 +89,7 @@ impl Comms {
     pub fn try_init(cli: &CliArgs, settings: &UserSettings) -> Result<Self> {
         let rt = tokio::runtime::Builder::new_multi_thread()
             .enable_io()
+            .enable_time()
             .build()
             .expect("failed to initialize tokio runtime");
 
@@ -63,52 +100,155 @@ impl Comms {
         // local game events -> outgoing messages
         let (outgoing_tx, outgoing_rx) = mpsc::channel(1024);
 
+        // for testing, put a fake network between the game and the websocket
+        let conditions = netsim::NetSimSettings::from_cli(cli, settings);
+        let (incoming_tx, outgoing_rx) = if conditions.is_enabled() {
+            log::warn!("simulating network conditions: {conditions:?}");
+
+            let (sim_incoming_tx, sim_incoming_rx) = mpsc::channel(1024);
+            rt.spawn(netsim::simulate(conditions.clone(), "incoming", sim_incoming_rx, incoming_tx));
+
+            let (sim_outgoing_tx, sim_outgoing_rx) = mpsc::channel(1024);
+            rt.spawn(netsim::simulate(conditions, "outgoing", outgoing_rx, sim_outgoing_tx));
+
+            (sim_incoming_tx, sim_outgoing_rx)
+        } else {
+            (incoming_tx, outgoing_rx)
+        };
+
 
         // the communicator changes states -> displayed as in-game diagnostics
         let (status_tx, status_rx) = mpsc::channel(4);
+
+        // messages between us and the remote user -> shown to any spectators
+        let (spectator_tx, _) = broadcast::channel(1024);
+
+        // the code on the command line wins over the configured one
+        let pairing_code = match &cli.mode {
+            ConnectionMode::Listen { pairing_code: Some(code), .. }
+            | ConnectionMode::Connect { pairing_code: Some(code), .. }
+            | ConnectionMode::Spectate { pairing_code: Some(code), .. } => Some(code.clone()),
+            _ => settings.pairing_code
+                .as_deref()
+                .map(pairing::parse_pairing_code)
+                .transpose()
+                .context("invalid pairing code in settings")?,
+        };
         
         let ctn = ConnectionContext {
             incoming_tx,
             outgoing_rx,
             status_tx,
+            spectator_tx,
+            pairing_code,
         };
 
         match &cli.mode {
-            ConnectionMode::Listen { port } => {
+            ConnectionMode::Listen { port, tls, .. } => {
                 // specify on the command line, or fall back to the settingsured settings
                 let port = port.unwrap_or(settings.port);
+                let tls = *tls || settings.use_tls;
 
                 let ip = settings.host_addr;
                 let listen_at = SocketAddr::new(ip, port);
 
+                let host_tls = if tls {
+                    let host_tls = HostTls::load_or_generate()
+                        .context("setting up TLS")?;
+                    log::info!("TLS certificate fingerprint: {}", host_tls.fingerprint);
+                    Some(host_tls)
+                } else {
+                    None
+                };
+
                 // local game events -> outgoing remote messages
 
                 // start up the listening thread
-                let task = ctn.listen_for_incoming(listen_at);
+                let task = ctn.listen_for_incoming(listen_at, host_tls);
+                rt.spawn(task);
+
+                // let everyone on the local network know where to find us
+                if settings.announce_on_lan {
+                    let announcement = discovery::Announcement::new(settings.player_name.clone(), port, tls);
+                    rt.spawn(discovery::announce(announcement, settings.discovery_port));
+                }
+            }
+            ConnectionMode::Connect { remote_addr, remote_port, tls, .. } => {
+                // fall back to the configured settings
+                let port = remote_port.unwrap_or(settings.port);
+                let tls = *tls || settings.use_tls;
+
+                let remote_url = remote_addr.to_url(port, tls, &[])
+                    .with_context(|| format!(
+                            "unable to make url, configured from cli args: remote_addr = {remote_addr}, remote_port = {remote_port:?}, settings.port = {}",
+                            settings.port
+                    ))?;
+
+                // a listening host always starts with the pairing handshake
+                let task = ctn.connect_to_remote(remote_url, true);
+                rt.spawn(task);
+            }
+            ConnectionMode::Join { relay, room, relay_port } => {
+                // fall back to the configured settings
+                let port = relay_port.unwrap_or(settings.port);
+
+                // relays do not serve TLS themselves, so only use it when asked for in the address
+                let mut relay_url = relay.to_url(port, false, &[super::relay::ROOM_PATH, room])
+                    .with_context(|| format!(
+                            "unable to make url, configured from cli args: relay = {relay}, room = {room}, relay_port = {relay_port:?}, settings.port = {}",
+                            settings.port
+                    ))?;
+                // if we are the first to join, this is how many people the room is for
+                relay_url.query_pairs_mut()
+                    .append_pair("players", cli.players.to_string().as_str());
+
+                // the relay just passes messages along, there is nobody to pair with
+                let task = ctn.connect_to_remote(relay_url, false);
                 rt.spawn(task);
             }
-            ConnectionMode::Connect { remote_addr, remote_port } => {
+            ConnectionMode::Spectate { remote_addr, room, remote_port, tls, .. } => {
                 // fall back to the configured settings
                 let port = remote_port.unwrap_or(settings.port);
 
-                let url = format!("ws://{remote_addr}:{port}");
-                let remote_url = Url::parse(url.as_str())
+                let segments = match room {
+                    Some(room) => vec![super::relay::ROOM_PATH, room.as_str(), SPECTATE_PATH],
+                    None => vec![SPECTATE_PATH],
+                };
+                // a listening host serves spectators over the same TLS as its remote user, relays do not
+                let tls = room.is_none() && (*tls || settings.use_tls);
+
+                let remote_url = remote_addr.to_url(port, tls, segments.as_slice())
                     .with_context(|| format!(
-                            "unable to parse url: {url}, configured from cli args: remote_addr = {remote_addr}, remote_port = {remote_port:?}, settings.port = {}",
+                            "unable to make url, configured from cli args: remote_addr = {remote_addr}, room = {room:?}, remote_port = {remote_port:?}, settings.port = {}",
                             settings.port
                     ))?;
 
-                let task = ctn.connect_to_remote(remote_url.clone());
+                // a listening host pairs with spectators too, the relay just passes messages along
+                let task = ctn.connect_to_remote(remote_url, room.is_none());
                 rt.spawn(task);
             }
-            ConnectionMode::Record { } => { /* nothing to do, everything will drop, it's fine */ }
+            ConnectionMode::Replay { capture, speed } => {
+                let entries = capture::read_capture(capture)?;
+                log::info!("replaying {} messages from {}", entries.len(), capture.display());
+
+                let task = ctn.replay_capture(entries, *speed);
+                rt.spawn(task);
+            }
+            ConnectionMode::Relay { .. } | ConnectionMode::Discover { .. } | ConnectionMode::Generate { .. } | ConnectionMode::Compose { .. } | ConnectionMode::CheckCharts { .. } | ConnectionMode::MigrateCharts { .. } => { /* these run headless, without the game */ }
+            ConnectionMode::Record { .. } | ConnectionMode::Local { } | ConnectionMode::Practice { } | ConnectionMode::Edit { .. } => { /* nothing to do, everything will drop, it's fine */ }
         }
 
+        let capture = cli.net_capture
+            .as_deref()
+            .map(CaptureWriter::create)
+            .transpose()?;
+
         Ok(Self {
             receive_msg: Some(incoming_rx),
             send_msg: Some(outgoing_tx),
             status_rx: Some(status_rx),
             net_status: NetStatus::Disconnected,
+            capture,
             // we need to keep the runtime around, other wise our tasks will be dropped
             _runtime: rt,
         })
@@ -148,7 +288,12 @@ impl Comms {
         use mpsc::error::TryRecvError::*;
         match self.receive_msg.as_mut()?.try_recv() {
             // If we successfully receive a message, return that
-            Ok(msg) => Some(msg),
+            Ok(msg) => {
+                if let Some(capture) = self.capture.as_mut() {
+                    capture.record(Direction::Incoming, &msg);
+                }
+                Some(msg)
+            }
             // If there's nothing at the moment, return none
             Err(Empty) => None,
             // If we've disconnected then we log it as an error
@@ -169,6 +314,10 @@ impl Comms {
                 bevy::log::warn_once!("no channel (outgoing_tx) settingsured");
                 return;
             };
+
+        if let Some(capture) = self.capture.as_mut() {
+            capture.record(Direction::Outgoing, &message);
+        }
         
         match send_msg.blocking_send(message) {
             Ok(_) => { },
@@ -209,6 +358,10 @@ struct ConnectionContext {
     incoming_tx: mpsc::Sender<GameMessage>,
     outgoing_rx: mpsc::Receiver<GameMessage>,
     status_tx: mpsc::Sender<NetStatus>,
+    /// Everything sent between the two users, wrapped up for spectators
+    spectator_tx: broadcast::Sender<GameMessage>,
+    /// When set, the remote user has to prove they know this before we exchange any game traffic
+    pairing_code: Option<String>,
 }
 impl ConnectionContext {
     async fn update_status(&mut self, msg: NetStatus) {
@@ -219,7 +372,7 @@ impl ConnectionContext {
             }
         }
     }
-    async fn listen_for_incoming(mut self, listen_at: SocketAddr) {
+    async fn listen_for_incoming(mut self, listen_at: SocketAddr, host_tls: Option<HostTls>) {
         self.update_status(NetStatus::Listening(format!(
             "attempting to listen at {listen_at}"
         ))).await;
@@ -234,37 +387,35 @@ impl ConnectionContext {
             .unwrap_or("<not found>".to_owned());
         log::info!("succesfully bound to {local_addr}");
 
+        // keep accepting in the background, so that spectators can join in the middle of a song
+        let (remote_user_tx, mut remote_user_rx) = mpsc::channel(1);
+        let fingerprint = host_tls.as_ref().map(|host_tls| host_tls.fingerprint.clone());
+        let acceptor = accept_connections(
+            listener,
+            host_tls,
+            remote_user_tx,
+            self.spectator_tx.clone(),
+            self.status_tx.clone(),
+            self.pairing_code.clone(),
+        );
+        tokio::spawn(acceptor);
+
         loop {
             log::info!("waiting for a connection on {local_addr}");
             self.update_status(NetStatus::Listening(format!(
                 "waiting for a connection on {local_addr}"
             ))).await;
 
-            let stream = match listener.accept().await {
-                Ok((stream, _)) => stream,
-                Err(e) => {
-                    log::error!("failed to connect: {e}");
-                    self.update_status(NetStatus::Error(format!(
-                        "failed to accept connection: {e}"
-                    ))).await;
-                    continue;
-                }
-            };
-
-            log::info!("accepted connection, attempting to upgrade to websocket");
-
-            let ws_stream = match tokio_tungstenite::accept_async(stream).await {
-                Ok(ws) => ws,
-                Err(e) => {
-                    log::error!("failed to upgrade websocket: {e}");
-                    self.update_status(NetStatus::Error(format!(
-                        "failed to upgrade connection: {e}"
-                    ))).await;
-                    continue;
-                }
+            let Some(mut ws_stream) = remote_user_rx.recv().await else {
+                log::error!("no longer accepting connections on {local_addr}");
+                return;
             };
 
-            log::info!("new websocket connection");
+            let handshake = pairing::challenge(&mut ws_stream, self.pairing_code.as_deref(), fingerprint.as_ref()).await;
+            if let Err(e) = handshake {
+                log::warn!("turning away connection: {e:#}");
+                continue;
+            }
 
             self.handle_connection(ws_stream).await;
 
@@ -272,16 +423,17 @@ impl ConnectionContext {
         }
     }
 
-    /// Connects to the remote and listens for updates to game state
-    async fn connect_to_remote(mut self, remote: Url) {
+    /// Connects to the remote and listens for updates to game state.
+    /// Set `handshake` when the remote is a listening host, which expects us to answer its pairing handshake.
+    async fn connect_to_remote(mut self, remote: Url, handshake: bool) {
         loop {
             log::info!("attempting to connect to remote");
             self.update_status(NetStatus::Connecting(format!(
                 "attempting to connect to {remote}"
             ))).await;
 
-            let ws_stream = match tokio_tungstenite::connect_async(remote.clone()).await {
-                Ok((ws, _)) => ws,
+            let (mut ws_stream, fingerprint) = match self.connect_to_any_address(&remote).await {
+                Ok(connected) => connected,
                 Err(e) => {
                     log::error!("failed to connect to remote: {e}");
                     self.update_status(NetStatus::Error(format!(
@@ -294,11 +446,102 @@ impl ConnectionContext {
 
             log::info!("new websocket connection");
 
+            if handshake {
+                let answered = pairing::answer(&mut ws_stream, self.pairing_code.as_deref(), fingerprint.as_ref()).await;
+                if let Err(e) = answered {
+                    log::error!("handshake with remote failed: {e:#}");
+                    self.update_status(NetStatus::Error(format!(
+                        "handshake with remote failed: {e:#}"
+                    ))).await;
+                    return;
+                }
+            }
+            if let (Some(fingerprint), None) = (&fingerprint, &self.pairing_code) {
+                log::warn!("connection is encrypted, but nothing checked who is on the other end. Their certificate fingerprint is {fingerprint}");
+            }
+
             self.handle_connection(ws_stream).await;
         }
 
     }
 
+    /// Resolves the host of the url and tries each address in turn, until one of them accepts the websocket.
+    /// For wss:// urls, also returns the fingerprint of the remote's certificate.
+    async fn connect_to_any_address(&mut self, remote: &Url) -> Result<(WebSocketStream<BoxedTransport>, Option<Fingerprint>)> {
+        let use_tls = match remote.scheme() {
+            "ws" => false,
+            "wss" => true,
+            scheme => anyhow::bail!("unsupported scheme {scheme}:// in {remote}"),
+        };
+
+        let port = remote.port_or_known_default()
+            .with_context(|| format!("no port in {remote}"))?;
+
+        // certificates name the host without the brackets around IPv6 addresses
+        let server_name = match remote.host() {
+            Some(Host::Domain(domain)) => domain.to_owned(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            None => anyhow::bail!("no host in {remote}"),
+        };
+
+        let addrs: Vec<SocketAddr> = match remote.host() {
+            Some(Host::Domain(domain)) => {
+                self.update_status(NetStatus::Connecting(format!(
+                    "looking up {domain}"
+                ))).await;
+                lookup_host((domain, port)).await
+                    .with_context(|| format!("unable to look up {domain}"))?
+                    .collect()
+            }
+            Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+            Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+            None => anyhow::bail!("no host in {remote}"),
+        };
+
+        let mut last_error = anyhow!("{remote} did not resolve to any addresses");
+
+        for addr in addrs {
+            log::info!("attempting to connect to {addr} for {remote}");
+            self.update_status(NetStatus::Connecting(format!(
+                "attempting to connect to {remote} at {addr}"
+            ))).await;
+
+            let stream = match TcpStream::connect(addr).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("failed to connect to {addr}: {e}");
+                    last_error = anyhow!(e).context(format!("connecting to {addr}"));
+                    continue;
+                }
+            };
+
+            let (stream, fingerprint): (BoxedTransport, _) = if use_tls {
+                match tls::connect(server_name.as_str(), stream).await {
+                    Ok((stream, fingerprint)) => (stream, Some(fingerprint)),
+                    Err(e) => {
+                        log::warn!("failed to start TLS with {addr}: {e:#}");
+                        last_error = e.context(format!("starting TLS with {addr}"));
+                        continue;
+                    }
+                }
+            } else {
+                (Box::new(stream), None)
+            };
+
+            // the request still carries the host name, which matters for virtual hosts
+            match tokio_tungstenite::client_async(remote.as_str(), stream).await {
+                Ok((ws, _)) => return Ok((ws, fingerprint)),
+                Err(e) => {
+                    log::warn!("failed to upgrade connection to {addr}: {e}");
+                    last_error = anyhow!(e).context(format!("upgrading connection to {addr}"));
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
 
     /// Runs until the connection to remote is lost
     async fn handle_connection<S>(&mut self, ws_stream: S)
@@ -341,6 +584,8 @@ impl ConnectionContext {
                             .inspect_err(|e| log::warn!("bad request: {e}"))
                             else { continue; };
 
+                        self.show_spectators(Team::Enemy, &incoming);
+
                         let Ok(_) = self.incoming_tx.send(incoming).await
                             .inspect_err(|e| {
                                 log::error!("unable to send to remote message channel: {e}")
@@ -366,6 +611,8 @@ impl ConnectionContext {
                             .inspect_err(|e| log::error!("serialization failed: {e}"))
                             else { continue; };
 
+                        self.show_spectators(Team::Player, &outgoing);
+
                         let outgoing_ws_msg = WsMessage::text(outgoing_json);
 
                         log::debug!("sending: {outgoing_ws_msg:?}");
@@ -382,6 +629,196 @@ impl ConnectionContext {
 
         }
 
+    /// Feeds the captured messages back in as if they came from the remote, at the pace they were captured.
+    /// `speed` multiplies how fast they play back.
+    async fn replay_capture(mut self, entries: Vec<CaptureEntry>, speed: f64) {
+        self.update_status(NetStatus::Connected).await;
+
+        let start = tokio::time::Instant::now();
+        for entry in entries {
+            // a slow enough replay speed can still push a time too far off to wait for
+            let Ok(offset) = std::time::Duration::try_from_secs_f64(entry.elapsed_secs() / speed)
+                .inspect_err(|e| log::error!("unable to replay message captured at {}s at speed {speed}: {e}", entry.elapsed_secs()))
+                else { break; };
+            let Some(due) = start.checked_add(offset) else {
+                log::error!("message captured at {}s is too far off to replay at speed {speed}", entry.elapsed_secs());
+                break;
+            };
+            tokio::time::sleep_until(due).await;
+
+            let Ok(_) = self.incoming_tx.send(entry.into_replayed()).await
+                .inspect_err(|e| {
+                    log::error!("unable to send to remote message channel: {e}")
+                })
+                else { break; };
+        }
+
+        log::info!("finished replaying capture");
+        self.update_status(NetStatus::Disconnected).await;
+    }
+
+    /// Passes a message between us and the remote user on to anyone spectating
+    fn show_spectators(&self, seat: Team, message: &GameMessage) {
+        if self.spectator_tx.receiver_count() == 0 {
+            return; // nobody is watching
+        }
+        let spectated = GameMessage::Spectated {
+            seat,
+            message: Box::new(message.clone()),
+        };
+        if let Err(e) = self.spectator_tx.send(spectated) {
+            log::debug!("no spectators to receive message: {e}");
+        }
+    }
 
 }
 
+/// Accepts every connection on the listener, starting TLS first if we have it.
+/// Spectators are served once they pair, the remote user is handed off through `remote_user_tx`.
+async fn accept_connections(
+    listener: TcpListener,
+    host_tls: Option<HostTls>,
+    remote_user_tx: mpsc::Sender<WebSocketStream<BoxedTransport>>,
+    spectator_tx: broadcast::Sender<GameMessage>,
+    status_tx: mpsc::Sender<NetStatus>,
+    pairing_code: Option<String>,
+) {
+    let fingerprint = host_tls.as_ref().map(|host_tls| host_tls.fingerprint.clone());
+    use mpsc::error::TrySendError;
+
+    let update_status = |msg: NetStatus| {
+        let status_tx = status_tx.clone();
+        async move {
+            if let Err(e) = status_tx.send(msg).await {
+                log::error!("error updating status at status_tx: {e}");
+            }
+        }
+    };
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::error!("failed to connect: {e}");
+                update_status(NetStatus::Error(format!(
+                    "failed to accept connection: {e}"
+                ))).await;
+                continue;
+            }
+        };
+
+        let stream: BoxedTransport = match &host_tls {
+            Some(host_tls) => match host_tls.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("failed to start TLS: {e:#}");
+                    update_status(NetStatus::Error(format!(
+                        "failed to start TLS: {e:#}"
+                    ))).await;
+                    continue;
+                }
+            },
+            None => Box::new(stream),
+        };
+
+        log::info!("accepted connection, attempting to upgrade to websocket");
+
+        // the request path tells us if this is a spectator
+        let mut path = String::new();
+        #[allow(clippy::result_large_err)] // the signature is up to tungstenite
+        let record_path = |req: &Request, resp: Response| -> Result<Response, ErrorResponse> {
+            path = req.uri().path().to_owned();
+            Ok(resp)
+        };
+
+        let ws_stream = match tokio_tungstenite::accept_hdr_async(stream, record_path).await {
+            Ok(ws) => ws,
+            Err(e) => {
+                log::error!("failed to upgrade websocket: {e}");
+                update_status(NetStatus::Error(format!(
+                    "failed to upgrade connection: {e}"
+                ))).await;
+                continue;
+            }
+        };
+
+        if path.trim_matches('/') == SPECTATE_PATH {
+            log::info!("new spectator connection");
+            let mut ws_stream = ws_stream;
+            let pairing_code = pairing_code.clone();
+            let fingerprint = fingerprint.clone();
+            let spectated_rx = spectator_tx.subscribe();
+            // pair in the background, so a slow spectator does not hold up anyone else connecting
+            tokio::spawn(async move {
+                let handshake = pairing::challenge(&mut ws_stream, pairing_code.as_deref(), fingerprint.as_ref()).await;
+                if let Err(e) = handshake {
+                    log::warn!("turning away spectator: {e:#}");
+                    return;
+                }
+                serve_spectator(ws_stream, spectated_rx).await;
+            });
+            continue;
+        }
+
+        log::info!("new websocket connection");
+
+        match remote_user_tx.try_send(ws_stream) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                log::warn!("already have a remote user waiting, turning away connection");
+            }
+            Err(TrySendError::Closed(_)) => {
+                log::info!("no longer listening for remote users");
+                return;
+            }
+        }
+    }
+}
+
+/// Runs until the spectator disconnects, showing them everything that goes on between us and the remote user
+async fn serve_spectator<S>(ws_stream: S, mut spectated_rx: broadcast::Receiver<GameMessage>)
+    where S: Stream<Item = WsMessageResult> + Sink<WsMessage>
+{
+    use broadcast::error::RecvError;
+
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+
+    loop {
+        tokio::select! {
+
+            // spectators are read only, so all we care about is when they leave
+            incoming = ws_read.next() => {
+                match incoming {
+                    Some(Ok(incoming)) if !incoming.is_close() => {
+                        log::debug!("ignoring message from spectator: {incoming:?}");
+                    }
+                    _ => {
+                        log::info!("spectator closed connection");
+                        break;
+                    }
+                }
+            }
+
+            spectated = spectated_rx.recv() => {
+                let spectated = match spectated {
+                    Ok(spectated) => spectated,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("spectator fell behind, skipped {skipped} messages");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let Ok(spectated_json) = serde_json::to_string(&spectated)
+                    .inspect_err(|e| log::error!("serialization failed: {e}"))
+                    else { continue; };
+
+                let Ok(_) = ws_write.send(WsMessage::text(spectated_json))
+                    .await
+                    .inspect_err(|_| log::warn!("unable to send to spectator, closing connection"))
+                    else { break; };
+            }
+
+        } // end tokio::select!
+    } // end loop
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashSet,
+    net::{
+        Ipv4Addr,
+        SocketAddr,
+    },
+};
+
+use anyhow::{
+    Result,
+    Context,
+};
+use bevy::utils::Duration;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::net::UdpSocket;
+
+use crate::user_settings::UserSettings;
+
+use super::PROTOCOL_VERSION;
+
+/// Every announcement carries this, so that we can ignore anything else sent to the discovery port
+const GAME_ID: &str = "saffron-rhythm-duel";
+
+/// How often a listening user tells the local network about themselves
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest announcement we expect to receive
+const MAX_ANNOUNCEMENT_LEN: usize = 1024;
+
+/// Broadcast over UDP by a listening user so that others on the local network can find them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Announcement {
+    game: String,
+    /// The user friendly name of whoever is listening
+    name: String,
+    /// The port they are listening for connections on
+    port: u16,
+    /// Which version of `GameMessage` they speak
+    protocol_version: u32,
+    /// Whether they expect `wss://` connections
+    #[serde(default)]
+    tls: bool,
+}
+impl Announcement {
+    pub fn new(name: String, port: u16, tls: bool) -> Announcement {
+        Announcement {
+            game: GAME_ID.to_string(),
+            name,
+            port,
+            protocol_version: PROTOCOL_VERSION,
+            tls,
+        }
+    }
+}
+
+/// Broadcasts the announcement on the discovery port until the runtime shuts down
+pub async fn announce(announcement: Announcement, discovery_port: u16) {
+    let Ok(socket) = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await
+        .inspect_err(|e| log::error!("unable to bind socket for announcements: {e}"))
+        else { return; };
+
+    let Ok(_) = socket.set_broadcast(true)
+        .inspect_err(|e| log::error!("unable to enable broadcast for announcements: {e}"))
+        else { return; };
+
+    let Ok(payload) = serde_json::to_vec(&announcement)
+        .inspect_err(|e| log::error!("serialization failed: {e}"))
+        else { return; };
+
+    // the broadcast reaches the local network, and loopback reaches anyone on this machine,
+    // which still works on a machine with no network to broadcast on
+    let targets = [
+        SocketAddr::from((Ipv4Addr::BROADCAST, discovery_port)),
+        SocketAddr::from((Ipv4Addr::LOCALHOST, discovery_port)),
+    ];
+    log::info!("announcing {} on the local network at {} and {}", announcement.name, targets[0], targets[1]);
+
+    // each target only warns the first time it fails, rather than every interval
+    let mut failing = [false; 2];
+    let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        for (target, failing) in targets.iter().zip(failing.iter_mut()) {
+            match socket.send_to(payload.as_slice(), target).await {
+                Ok(_) => *failing = false,
+                Err(e) if !*failing => {
+                    log::warn!("unable to send announcement to {target}: {e}");
+                    *failing = true;
+                }
+                Err(e) => log::debug!("still unable to send announcement to {target}: {e}"),
+            }
+        }
+    }
+}
+
+/// Listens for announcements and prints out every host found. Does not start up the game.
+pub fn run_discover(wait: Duration, settings: &UserSettings) -> Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("failed to initialize tokio runtime")?;
+
+    rt.block_on(discover(wait, settings.discovery_port))
+}
+
+async fn discover(wait: Duration, discovery_port: u16) -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery_port)).await
+        .with_context(|| format!("binding to discovery port {discovery_port}"))?;
+
+    println!("looking for hosts on the local network for {} seconds...", wait.as_secs());
+
+    let mut found = HashSet::new();
+    let mut buf = [0u8; MAX_ANNOUNCEMENT_LEN];
+
+    let deadline = tokio::time::sleep(wait);
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+
+            received = socket.recv_from(&mut buf) => {
+                let Ok((len, from)) = received
+                    .inspect_err(|e| log::warn!("error receiving announcement: {e}"))
+                    else { continue; };
+
+                let Ok(announcement) = serde_json::from_slice::<Announcement>(&buf[..len])
+                    .inspect_err(|e| log::debug!("ignoring bad announcement from {from}: {e}"))
+                    else { continue; };
+
+                if announcement.game != GAME_ID {
+                    log::debug!("ignoring announcement for {} from {from}", announcement.game);
+                    continue;
+                }
+
+                // they tell us the port, but the address is wherever it came from
+                let host = SocketAddr::new(from.ip(), announcement.port);
+                if found.insert(host) {
+                    print_host(host, &announcement);
+                }
+            }
+        }
+    }
+
+    if found.is_empty() {
+        println!("no hosts found. Make sure they are listening on the same network, with discovery port {discovery_port}");
+    }
+
+    Ok(())
+}
+
+fn print_host(host: SocketAddr, announcement: &Announcement) {
+    println!("found {} at {host}", announcement.name);
+    if announcement.protocol_version != PROTOCOL_VERSION {
+        println!(
+            "    warning: they are on protocol version {}, but you are on {PROTOCOL_VERSION}. Make sure you are both on the same version of the game",
+            announcement.protocol_version
+        );
+    }
+    if announcement.tls {
+        println!("    connect with: connect {} --remote-port {} --tls", host.ip(), host.port());
+    } else {
+        println!("    connect with: connect {} --remote-port {}", host.ip(), host.port());
+    }
+}
@@ -12,11 +12,20 @@ use crate::{
 };
 use crate::team_markers::{
     PlayerMarker,
-    EnemyMarker
+    EnemyMarker,
+    Team,
 };
 
 use crate::lane::Lane;
 
+use crate::attacks::Attack;
+
+use crate::series::SeriesState;
+
+use crate::chat::Emote;
+
+use crate::coop::LaneHalf;
+
 use crate::judgement::grading::RemoteCorrectHitEvent;
 
 use crate::song::{
@@ -27,9 +36,19 @@ use crate::song::{
 pub mod communicate;
 pub mod widgets;
 pub mod translate;
+pub mod relay;
+pub mod discovery;
+pub mod address;
+pub mod tls;
+pub mod pairing;
+pub mod netsim;
+pub mod capture;
 
 use communicate::Comms;
 
+/// Bump this whenever `GameMessage` or the pairing handshake changes in a way that older versions can not read
+pub const PROTOCOL_VERSION: u32 = 7;
+
 /// Message sent from user to user to communicate game state.
 /// We will use this for local -> remote and remote -> local
 /// since comms are meant to be symmetric
@@ -44,7 +63,62 @@ pub enum GameMessage {
         chart_name: ChartName,
     },
     CorrectHit(RemoteCorrectHitEvent),
-    SyncSpawnerState(SyncSpawnerEvent<EnemyMarker>)
+    SyncSpawnerState(SyncSpawnerEvent<EnemyMarker>),
+    /// The sender launched an attack at the receiver's panel
+    Attack {
+        attack: Attack,
+    },
+    /// The sender's view of the match series
+    SeriesState(SeriesState),
+    /// The sender's final score for a round of the series
+    RoundScore {
+        round: u32,
+        score: u32,
+    },
+    /// A line of text chat
+    Chat {
+        text: String,
+    },
+    Emote {
+        emote: Emote,
+    },
+    /// One of the duelists' messages, passed along to a spectator.
+    /// The seat is which panel it belongs on, as seen from the listening host (or the first user in a relay room).
+    Spectated {
+        seat: Team,
+        message: Box<GameMessage>,
+    },
+    /// Which half of the lanes the sender plays in a co-op match
+    CoopHalf {
+        half: LaneHalf,
+        /// Whether the sender has already heard which half the receiver plays
+        heard_partner: bool,
+    },
+    /// The sender missed a note on one of their lanes in a co-op match
+    DroppedNote {
+        lane: Lane,
+        arrival_beat: f32,
+    },
+    /// Another user's message in a relay room of three or more, passed along by the relay.
+    /// The seat is which panel it belongs on, as seen by the receiver. Messages from the receiver's usual remote user are passed along as they are.
+    Relayed {
+        seat: Team,
+        message: Box<GameMessage>,
+    },
+}
+
+/// Spectators connect to `ws://<host>:<port>/<SPECTATE_PATH>`,
+/// or `ws://<relay>:<port>/room/<room code>/<SPECTATE_PATH>` on a relay.
+pub const SPECTATE_PATH: &str = "spectate";
+
+/// Run condition for everything that a spectator should not do, i.e. handle local input or talk back to the duelists.
+pub fn not_spectating(cli: Res<CliArgs>) -> bool {
+    !cli.mode.is_spectator()
+}
+
+/// Run condition for everything the enemy panel needs when it is played on this keyboard, i.e. in a hot-seat match.
+pub fn playing_locally(cli: Res<CliArgs>) -> bool {
+    cli.mode.is_hot_seat()
 }
 
 fn setup_comms(
@@ -85,13 +159,14 @@ impl Plugin for RemoteUserPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_systems(Startup, setup_comms)
+            .add_systems(Update, translate::translate_messages_from_remote)
             .add_systems(Update, (
-                    translate::translate_messages_from_remote,
                     translate::translate_events_from_local,
+                    translate::translate_chat_from_local,
                     sync_chart_progress_local_to_remote.run_if(
                         bevy::time::common_conditions::on_timer(CHART_SYNC_DURATION)
                     )
-            ))
+            ).run_if(not_spectating))
             .add_plugins(widgets::NetworkingWidgetsPlugin)
         ;
     }
@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+
+use anyhow::{
+    Result,
+    Context,
+};
+use bevy::utils::Duration;
+use rand::Rng;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    CliArgs,
+    user_settings::UserSettings,
+};
+
+/// How much longer than usual a reordered message is held back, on top of the simulated latency
+const REORDER_HOLD_BACK: Duration = Duration::from_millis(250);
+
+/// Fake network conditions, applied to every `GameMessage` to and from the remote user.
+/// This is only meant for reproducing sync bugs on one machine, so everything is off by default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NetSimSettings {
+    /// Delay every message by this many milli seconds
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Each message is delayed up to this many milli seconds more or less than `latency_ms`
+    #[serde(default)]
+    pub jitter_ms: u64,
+    /// Chance from 0 to 1 that a message is dropped
+    #[serde(default)]
+    pub loss: f32,
+    /// Chance from 0 to 1 that a message is held back, so that the ones after it overtake it
+    #[serde(default)]
+    pub reorder: f32,
+}
+impl NetSimSettings {
+    /// The configured settings, with anything given on the command line taking priority
+    pub fn from_cli(cli: &CliArgs, settings: &UserSettings) -> NetSimSettings {
+        let configured = &settings.net_sim;
+        NetSimSettings {
+            latency_ms: cli.sim_latency_ms.unwrap_or(configured.latency_ms),
+            jitter_ms: cli.sim_jitter_ms.unwrap_or(configured.jitter_ms),
+            loss: cli.sim_loss.unwrap_or(configured.loss),
+            reorder: cli.sim_reorder.unwrap_or(configured.reorder),
+        }
+    }
+
+    /// Settings files are not checked like the command line is, so chances that are not from 0 to 1 are turned off
+    pub fn validated(self) -> NetSimSettings {
+        let validate = |name: &str, chance: f32| {
+            if (0.0..=1.0).contains(&chance) {
+                chance
+            } else {
+                log::warn!("net_sim.{name} must be between 0 and 1, got {chance}. Turning it off");
+                0.0
+            }
+        };
+        NetSimSettings {
+            loss: validate("loss", self.loss),
+            reorder: validate("reorder", self.reorder),
+            ..self
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.latency_ms > 0 || self.jitter_ms > 0 || self.loss > 0.0 || self.reorder > 0.0
+    }
+
+    /// How long to hold on to the next message, or `None` if it gets lost
+    fn pick_delay(&self) -> Option<Duration> {
+        let mut rng = rand::thread_rng();
+
+        if rng.gen_bool(as_chance(self.loss)) {
+            return None;
+        }
+
+        let jitter = self.jitter_ms as i64;
+        let delay_ms = (self.latency_ms as i64 + rng.gen_range(-jitter..=jitter)).max(0);
+        let mut delay = Duration::from_millis(delay_ms as u64);
+
+        if rng.gen_bool(as_chance(self.reorder)) {
+            delay += REORDER_HOLD_BACK;
+        }
+
+        Some(delay)
+    }
+}
+
+/// A chance that `gen_bool` will take. It panics on NaN, which clamping lets through
+fn as_chance(chance: f32) -> f64 {
+    if chance.is_finite() {
+        chance.clamp(0.0, 1.0) as f64
+    } else {
+        0.0
+    }
+}
+
+/// Chances are given as a number from 0 to 1
+pub fn parse_chance(source: &str) -> Result<f32> {
+    let chance: f32 = source.trim().parse()
+        .with_context(|| format!("{source} is not a number"))?;
+    if !(0.0..=1.0).contains(&chance) {
+        anyhow::bail!("chance must be between 0 and 1, got {chance}");
+    }
+    Ok(chance)
+}
+
+/// Passes everything from `rx` on to `tx`, under the simulated network conditions.
+/// Runs until `rx` closes. `direction` is only used for logging.
+pub async fn simulate<M>(
+    conditions: NetSimSettings,
+    direction: &'static str,
+    mut rx: mpsc::Receiver<M>,
+    tx: mpsc::Sender<M>,
+)
+    where M: Debug + Send + 'static
+{
+    while let Some(message) = rx.recv().await {
+        let Some(delay) = conditions.pick_delay() else {
+            log::debug!("simulating loss of {direction} message: {message:?}");
+            continue;
+        };
+
+        // every message waits on its own, so that jitter can reorder them
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = tx.send(message).await {
+                log::debug!("dropping delayed {direction} message, channel closed: {e}");
+            }
+        });
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{
+    Result,
+    Context,
+    anyhow,
+};
+use bevy::utils::Duration;
+use futures_util::{
+    SinkExt,
+    StreamExt,
+};
+use ring::hmac;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::protocol::Message as WsMessage;
+
+use super::{
+    PROTOCOL_VERSION,
+    tls::{
+        BoxedTransport,
+        Fingerprint,
+    },
+};
+
+/// How long either side waits on the other during the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shortest pairing code we will accept
+const MIN_PAIRING_CODE_LEN: usize = 4;
+
+/// Length of the random nonce each side contributes, in bytes
+const NONCE_LEN: usize = 32;
+
+pub fn parse_pairing_code(source: &str) -> Result<String> {
+    let code = source.trim();
+    if code.len() < MIN_PAIRING_CODE_LEN {
+        anyhow::bail!("pairing code must be at least {MIN_PAIRING_CODE_LEN} characters, got {}", code.len());
+    }
+    Ok(code.to_string())
+}
+
+/// Sent on every direct connection, before any `GameMessage`s.
+/// When a pairing code is set, each side proves it knows the code without sending it,
+/// by signing both nonces and the fingerprint of the host's certificate.
+#[derive(Debug, Serialize, Deserialize)]
+enum Handshake {
+    /// Host to client, as soon as the websocket is open
+    Hello {
+        protocol_version: u32,
+        nonce: String,
+        pairing_required: bool,
+    },
+    /// Client to host
+    Answer {
+        nonce: String,
+        proof: Option<String>,
+    },
+    /// Host to client, game traffic starts after this
+    Accepted {
+        proof: Option<String>,
+    },
+    /// Host to client, the host closes the connection after this
+    Rejected {
+        reason: String,
+    },
+}
+
+/// Which side is signing, so that a proof from one side can not be sent back as if it was from the other
+#[derive(Debug, Clone, Copy)]
+enum Role {
+    Host,
+    Client,
+}
+impl Role {
+    fn label(self) -> &'static [u8] {
+        match self {
+            Role::Host => b"host",
+            Role::Client => b"client",
+        }
+    }
+}
+
+/// What both sides sign over
+struct Transcript<'a> {
+    host_nonce: &'a [u8],
+    client_nonce: &'a [u8],
+    /// Without TLS there is no certificate to tie the proof to
+    fingerprint: Option<&'a Fingerprint>,
+}
+impl Transcript<'_> {
+    fn sign(&self, code: &str, role: Role) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, code.as_bytes());
+        to_hex(hmac::sign(&key, self.message(role).as_slice()).as_ref())
+    }
+    fn verify(&self, code: &str, role: Role, proof: &str) -> bool {
+        let Some(proof) = from_hex(proof) else {
+            return false;
+        };
+        let key = hmac::Key::new(hmac::HMAC_SHA256, code.as_bytes());
+        // constant time comparison, so the proof can not be guessed byte by byte
+        hmac::verify(&key, self.message(role).as_slice(), proof.as_slice()).is_ok()
+    }
+    fn message(&self, role: Role) -> Vec<u8> {
+        [
+            role.label(),
+            self.host_nonce,
+            self.client_nonce,
+            self.fingerprint.map(Fingerprint::as_bytes).unwrap_or_default(),
+        ].concat()
+    }
+}
+
+/// Runs the host side of the handshake. Returns an error if the client should be turned away.
+pub async fn challenge(
+    ws: &mut WebSocketStream<BoxedTransport>,
+    pairing_code: Option<&str>,
+    fingerprint: Option<&Fingerprint>,
+) -> Result<()> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, run_challenge(ws, pairing_code, fingerprint))
+        .await
+        .context("client took too long to answer the handshake")?
+}
+
+async fn run_challenge(
+    ws: &mut WebSocketStream<BoxedTransport>,
+    pairing_code: Option<&str>,
+    fingerprint: Option<&Fingerprint>,
+) -> Result<()> {
+    let host_nonce = new_nonce();
+    send(ws, &Handshake::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        nonce: to_hex(host_nonce.as_slice()),
+        pairing_required: pairing_code.is_some(),
+    }).await?;
+
+    let Handshake::Answer { nonce, proof } = recv(ws).await? else {
+        anyhow::bail!("expected an answer to the handshake");
+    };
+
+    let Some(code) = pairing_code else {
+        // anyone may connect
+        return send(ws, &Handshake::Accepted { proof: None }).await;
+    };
+
+    let client_nonce = from_hex(nonce.as_str())
+        .filter(|nonce| nonce.len() == NONCE_LEN)
+        .ok_or_else(|| anyhow!("client sent a malformed nonce"))?;
+    let transcript = Transcript {
+        host_nonce: host_nonce.as_slice(),
+        client_nonce: client_nonce.as_slice(),
+        fingerprint,
+    };
+
+    let verified = proof
+        .is_some_and(|proof| transcript.verify(code, Role::Client, proof.as_str()));
+    if !verified {
+        // best effort, they are getting disconnected either way
+        let _ = send(ws, &Handshake::Rejected { reason: "wrong pairing code".to_owned() }).await;
+        let _ = ws.close(None).await;
+        anyhow::bail!("client did not know the pairing code");
+    }
+
+    send(ws, &Handshake::Accepted {
+        proof: Some(transcript.sign(code, Role::Host)),
+    }).await
+}
+
+/// Runs the client side of the handshake. Returns an error if we should not trust the host.
+pub async fn answer(
+    ws: &mut WebSocketStream<BoxedTransport>,
+    pairing_code: Option<&str>,
+    fingerprint: Option<&Fingerprint>,
+) -> Result<()> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, run_answer(ws, pairing_code, fingerprint))
+        .await
+        .context("host took too long to start the handshake")?
+}
+
+async fn run_answer(
+    ws: &mut WebSocketStream<BoxedTransport>,
+    pairing_code: Option<&str>,
+    fingerprint: Option<&Fingerprint>,
+) -> Result<()> {
+    let Handshake::Hello { protocol_version, nonce, pairing_required } = recv(ws).await? else {
+        anyhow::bail!("expected the host to start the handshake");
+    };
+
+    if protocol_version != PROTOCOL_VERSION {
+        anyhow::bail!("host is on protocol version {protocol_version}, but you are on {PROTOCOL_VERSION}. Make sure you are both on the same version of the game");
+    }
+
+    let code = match (pairing_code, pairing_required) {
+        (Some(code), true) => code,
+        (None, false) => {
+            send(ws, &Handshake::Answer { nonce: String::new(), proof: None }).await?;
+            return expect_accepted(ws).await.map(|_| ());
+        }
+        (None, true) => anyhow::bail!("host requires a pairing code, supply one with --pairing-code"),
+        // otherwise anyone could pretend to be the host, just by not asking
+        (Some(_), false) => anyhow::bail!("host did not ask for the pairing code, so we can not be sure who they are"),
+    };
+
+    let host_nonce = from_hex(nonce.as_str())
+        .filter(|nonce| nonce.len() == NONCE_LEN)
+        .ok_or_else(|| anyhow!("host sent a malformed nonce"))?;
+    let client_nonce = new_nonce();
+    let transcript = Transcript {
+        host_nonce: host_nonce.as_slice(),
+        client_nonce: client_nonce.as_slice(),
+        fingerprint,
+    };
+
+    send(ws, &Handshake::Answer {
+        nonce: to_hex(client_nonce.as_slice()),
+        proof: Some(transcript.sign(code, Role::Client)),
+    }).await?;
+
+    let proof = expect_accepted(ws).await?
+        .ok_or_else(|| anyhow!("host accepted without proving it knows the pairing code"))?;
+
+    if !transcript.verify(code, Role::Host, proof.as_str()) {
+        anyhow::bail!("host does not know the pairing code");
+    }
+    Ok(())
+}
+
+/// Waits for the host's verdict, returning their proof if they sent one
+async fn expect_accepted(ws: &mut WebSocketStream<BoxedTransport>) -> Result<Option<String>> {
+    match recv(ws).await? {
+        Handshake::Accepted { proof } => Ok(proof),
+        Handshake::Rejected { reason } => anyhow::bail!("host turned us away: {reason}"),
+        other => anyhow::bail!("unexpected handshake message: {other:?}"),
+    }
+}
+
+async fn send(ws: &mut WebSocketStream<BoxedTransport>, message: &Handshake) -> Result<()> {
+    let json = serde_json::to_string(message)
+        .context("serializing handshake")?;
+    ws.send(WsMessage::text(json)).await
+        .context("sending handshake")
+}
+
+async fn recv(ws: &mut WebSocketStream<BoxedTransport>) -> Result<Handshake> {
+    loop {
+        let message = ws.next().await
+            .ok_or_else(|| anyhow!("connection closed during handshake"))?
+            .context("reading handshake")?;
+
+        if message.is_close() {
+            anyhow::bail!("connection closed during handshake");
+        }
+        // pings and pongs are handled for us
+        let Ok(text) = message.to_text() else { continue; };
+        if text.is_empty() {
+            continue;
+        }
+
+        return serde_json::from_str(text)
+            .with_context(|| format!("bad handshake message: {text}"));
+    }
+}
+
+fn new_nonce() -> Vec<u8> {
+    rand::random::<[u8; NONCE_LEN]>().to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(source: &str) -> Option<Vec<u8>> {
+    if !source.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..source.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(source.get(i..i + 2)?, 16).ok())
+        .collect()
+}
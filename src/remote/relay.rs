@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+use anyhow::{
+    Result,
+    Context,
+};
+use futures_util::{
+    SinkExt,
+    StreamExt,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use warp::{
+    Filter,
+    Rejection,
+    ws::{
+        Message,
+        WebSocket,
+        Ws,
+    },
+};
+
+use crate::user_settings::UserSettings;
+use crate::team_markers::{
+    Team,
+    MAX_PLAYERS,
+};
+
+use super::{
+    GameMessage,
+    SPECTATE_PATH,
+};
+
+/// Clients connect to `ws://<relay>:<port>/<ROOM_PATH>/<room code>`,
+/// spectators to `ws://<relay>:<port>/<ROOM_PATH>/<room code>/<SPECTATE_PATH>`
+pub const ROOM_PATH: &str = "room";
+
+/// How many clients can be paired up in one room, unless the first one to join asks for more
+const DEFAULT_ROOM_CAPACITY: usize = 2;
+
+/// Longest room code we will accept
+const MAX_ROOM_CODE_LEN: usize = 32;
+
+/// Room codes end up in a url path, so we keep them to a conservative character set.
+pub fn parse_room_code(source: &str) -> Result<String> {
+    let code = source.trim();
+    if code.is_empty() {
+        anyhow::bail!("room code can not be empty");
+    }
+    if code.len() > MAX_ROOM_CODE_LEN {
+        anyhow::bail!("room code can be at most {MAX_ROOM_CODE_LEN} characters, got {}", code.len());
+    }
+    if let Some(c) = code.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+        anyhow::bail!("room code may only contain letters, digits, '-' and '_', found {c:?}");
+    }
+    Ok(code.to_string())
+}
+
+/// A match needs somebody to play against, and the layout has room for at most `MAX_PLAYERS` panels
+pub fn parse_player_count(source: &str) -> Result<usize> {
+    let players: usize = source.trim().parse()?;
+    if !(2..=MAX_PLAYERS).contains(&players) {
+        anyhow::bail!("a match needs from 2 to {MAX_PLAYERS} players, got {players}");
+    }
+    Ok(players)
+}
+
+#[derive(Debug, Deserialize)]
+/// Query parameters a client may give when joining a room
+struct JoinQuery {
+    /// How many clients the room is for. Only the first client to join gets to decide
+    players: Option<usize>,
+}
+
+/// Channel that writes to a single client's websocket
+type PeerTx = mpsc::UnboundedSender<Message>;
+
+#[derive(Debug)]
+/// A group of clients that forward messages to each other
+struct Room {
+    /// Each slot is occupied by at most one client. The room holds as many clients as it has slots
+    peers: Vec<Option<PeerTx>>,
+    /// Watch all of the clients, but never send anything
+    spectators: Vec<PeerTx>,
+}
+impl Default for Room {
+    fn default() -> Room {
+        Room::with_capacity(DEFAULT_ROOM_CAPACITY)
+    }
+}
+impl Room {
+    fn with_capacity(capacity: usize) -> Room {
+        Room {
+            peers: vec![None; capacity.clamp(2, MAX_PLAYERS)],
+            spectators: Vec::new(),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.peers.iter().all(Option::is_none) && self.spectators.is_empty()
+    }
+    /// Which panel spectators see this slot on. The first client in the room acts like a listening host.
+    fn seat_of(slot: usize) -> Team {
+        Team::from_seat(slot).unwrap_or(Team::Enemy)
+    }
+    /// Which panel the client in slot `to` sees the client in slot `from` on.
+    /// Everyone sees the rest of the room in the same order, starting from the slot after their own.
+    fn relative_seat(&self, from: usize, to: usize) -> Team {
+        let capacity = self.peers.len();
+        Room::seat_of((from + capacity - to) % capacity)
+    }
+    /// Puts the client in the first free slot, returning the slot index
+    fn claim_slot(&mut self, tx: PeerTx) -> Option<usize> {
+        let slot = self.peers.iter().position(Option::is_none)?;
+        self.peers[slot] = Some(tx);
+        Some(slot)
+    }
+    /// The other clients in the room that have joined, with their slot index
+    fn others_of(&self, slot: usize) -> impl Iterator<Item = (usize, &PeerTx)> {
+        self.peers
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| *i != slot)
+            .filter_map(|(i, peer)| Some((i, peer.as_ref()?)))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// All of the rooms the relay knows about, shared between the client tasks
+struct Rooms {
+    inner: Arc<Mutex<HashMap<String, Room>>>,
+}
+impl Rooms {
+    fn join(&self, code: &str, players: Option<usize>, tx: PeerTx) -> Option<usize> {
+        let mut rooms = self.inner.lock().expect("relay room lock poisoned");
+        let room = rooms
+            .entry(code.to_string())
+            .or_default();
+        // the first client decides how big the room is, even if spectators showed up before them
+        if let Some(players) = players.filter(|_| room.peers.iter().all(Option::is_none)) {
+            room.peers = Room::with_capacity(players).peers;
+        }
+        if players.is_some_and(|players| players != room.peers.len()) {
+            log::warn!("client asked for {players:?} players in room {code}, but it is for {}", room.peers.len());
+        }
+        room.claim_slot(tx)
+    }
+    fn spectate(&self, code: &str, tx: PeerTx) {
+        let mut rooms = self.inner.lock().expect("relay room lock poisoned");
+        rooms
+            .entry(code.to_string())
+            .or_default()
+            .spectators
+            .push(tx);
+    }
+    fn leave(&self, code: &str, slot: usize) {
+        let mut rooms = self.inner.lock().expect("relay room lock poisoned");
+        let Some(room) = rooms.get_mut(code) else {
+            return;
+        };
+        room.peers[slot] = None;
+        if room.is_empty() {
+            log::info!("room {code} is empty, closing it");
+            rooms.remove(code);
+        }
+    }
+    /// Forgets about spectators that have disconnected
+    fn prune_spectators(&self, code: &str) {
+        let mut rooms = self.inner.lock().expect("relay room lock poisoned");
+        let Some(room) = rooms.get_mut(code) else {
+            return;
+        };
+        room.spectators.retain(|tx| !tx.is_closed());
+        if room.is_empty() {
+            log::info!("room {code} is empty, closing it");
+            rooms.remove(code);
+        }
+    }
+    /// Passes the message along to the other clients in the room, and to anyone spectating
+    fn forward(&self, code: &str, from_slot: usize, text: &str, message: GameMessage) {
+        let rooms = self.inner.lock().expect("relay room lock poisoned");
+        let Some(room) = rooms.get(code) else {
+            return;
+        };
+
+        let mut forwarded = false;
+        for (to_slot, peer) in room.others_of(from_slot) {
+            forwarded = true;
+
+            // the client right after the sender sees them as their usual remote user, so two player rooms
+            // pass the message along untouched. Everyone else needs to be told which panel it goes on.
+            let sent = match room.relative_seat(from_slot, to_slot) {
+                Team::Enemy => peer.send(Message::text(text)),
+                seat => {
+                    let relayed = GameMessage::Relayed {
+                        seat,
+                        message: Box::new(message.clone()),
+                    };
+                    let Ok(relayed_json) = serde_json::to_string(&relayed)
+                        .inspect_err(|e| log::error!("serialization failed: {e}"))
+                        else { continue; };
+                    peer.send(Message::text(relayed_json))
+                }
+            };
+            if let Err(e) = sent {
+                log::warn!("unable to forward message in room {code}: {e}");
+            }
+        }
+        if !forwarded {
+            log::debug!("nobody else in room {code} yet, dropping message");
+        }
+
+        if room.spectators.is_empty() {
+            return;
+        }
+        let spectated = GameMessage::Spectated {
+            seat: Room::seat_of(from_slot),
+            message: Box::new(message),
+        };
+        let Ok(spectated_json) = serde_json::to_string(&spectated)
+            .inspect_err(|e| log::error!("serialization failed: {e}"))
+            else { return; };
+
+        room.spectators
+            .iter()
+            .for_each(|spectator| {
+                // disconnected spectators get cleaned up when their task finishes
+                let _ = spectator.send(Message::text(spectated_json.as_str()));
+            });
+    }
+}
+
+/// Runs the relay server until it is killed. Does not start up the game.
+pub fn run_relay(port: Option<u16>, settings: &UserSettings) -> Result<()> {
+    // specify on the command line, or fall back to the configured settings
+    let port = port.unwrap_or(settings.port);
+    let listen_at = SocketAddr::new(settings.host_addr, port);
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("failed to initialize tokio runtime")?;
+
+    rt.block_on(serve(listen_at))
+}
+
+/// Matches `/<ROOM_PATH>/<room code>`, rejecting invalid room codes
+fn room_code() -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    warp::path(ROOM_PATH)
+        .and(warp::path::param::<String>())
+        .and_then(|code: String| async move {
+            parse_room_code(code.as_str())
+                .inspect_err(|e| log::warn!("rejecting client: {e}"))
+                .map_err(|_| warp::reject::not_found())
+        })
+}
+
+async fn serve(listen_at: SocketAddr) -> Result<()> {
+    let rooms = Rooms::default();
+    let rooms = warp::any().map(move || rooms.clone());
+
+    let clients = room_code()
+        .and(warp::path::end())
+        .and(warp::query::<JoinQuery>())
+        .and(warp::ws())
+        .and(rooms.clone())
+        .map(|code: String, query: JoinQuery, ws: Ws, rooms: Rooms| {
+            ws.on_upgrade(move |socket| handle_client(socket, code, query.players, rooms))
+        });
+
+    let spectators = room_code()
+        .and(warp::path(SPECTATE_PATH))
+        .and(warp::path::end())
+        .and(warp::ws())
+        .and(rooms)
+        .map(|code: String, ws: Ws, rooms: Rooms| {
+            ws.on_upgrade(move |socket| handle_spectator(socket, code, rooms))
+        });
+
+    let routes = clients.or(spectators);
+
+    let (local_addr, server) = warp::serve(routes)
+        .try_bind_ephemeral(listen_at)
+        .with_context(|| format!("binding relay to {listen_at}"))?;
+
+    log::info!("relay listening on {local_addr}");
+    server.await;
+
+    Ok(())
+}
+
+/// Runs until the client disconnects, forwarding its messages to the other clients in the room
+async fn handle_client(socket: WebSocket, code: String, players: Option<usize>, rooms: Rooms) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let Some(slot) = rooms.join(code.as_str(), players, tx) else {
+        log::warn!("room {code} is full, turning away client");
+        let _ = socket.close().await;
+        return;
+    };
+    log::info!("client joined room {code} in slot {slot}");
+
+    let (mut ws_write, mut ws_read) = socket.split();
+
+    loop {
+        tokio::select! {
+
+            // read an incoming message from this client, and pass it on to everyone else
+            incoming = ws_read.next() => {
+                let Some(incoming) = incoming else {
+                    log::info!("client in room {code} closed connection");
+                    break;
+                };
+                let Ok(incoming) = incoming
+                    .inspect_err(|e| log::warn!("error reading message in room {code}: {e}, closing connection"))
+                    else { break; };
+
+                if incoming.is_close() {
+                    log::info!("client in room {code} closed connection");
+                    break;
+                }
+                // pings, pongs and binary frames are not game traffic
+                let Ok(text) = incoming.to_str() else { continue; };
+
+                // we only relay game traffic, drop anything else
+                let Ok(message) = serde_json::from_str::<GameMessage>(text)
+                    .inspect_err(|e| log::warn!("bad request in room {code}: {e}"))
+                    else { continue; };
+
+                // only the relay wraps messages with a seat, a client doing it could pose as someone else in the room
+                if matches!(message, GameMessage::Relayed { .. } | GameMessage::Spectated { .. }) {
+                    log::warn!("dropping message from client in room {code} that claims to come from another seat");
+                    continue;
+                }
+
+                rooms.forward(code.as_str(), slot, text, message);
+            }
+
+            // someone else sent something, write it to this client
+            outgoing = rx.recv() => {
+                let Some(outgoing) = outgoing else { break; };
+
+                let Ok(_) = ws_write.send(outgoing)
+                    .await
+                    .inspect_err(|e| log::warn!("unable to send on websocket in room {code}: {e}"))
+                    else { break; };
+            }
+
+        } // end tokio::select!
+    } // end loop
+
+    rooms.leave(code.as_str(), slot);
+    log::info!("client left room {code}");
+}
+
+/// Runs until the spectator disconnects, showing them every client in the room
+async fn handle_spectator(socket: WebSocket, code: String, rooms: Rooms) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    rooms.spectate(code.as_str(), tx);
+    log::info!("spectator joined room {code}");
+
+    let (mut ws_write, mut ws_read) = socket.split();
+
+    loop {
+        tokio::select! {
+
+            // spectators are read only, so all we care about is when they leave
+            incoming = ws_read.next() => {
+                match incoming {
+                    Some(Ok(incoming)) if !incoming.is_close() => {
+                        log::debug!("ignoring message from spectator in room {code}");
+                    }
+                    _ => {
+                        log::info!("spectator in room {code} closed connection");
+                        break;
+                    }
+                }
+            }
+
+            outgoing = rx.recv() => {
+                let Some(outgoing) = outgoing else { break; };
+
+                let Ok(_) = ws_write.send(outgoing)
+                    .await
+                    .inspect_err(|e| log::warn!("unable to send to spectator in room {code}: {e}"))
+                    else { break; };
+            }
+
+        } // end tokio::select!
+    } // end loop
+
+    // close our end first, so that we get pruned
+    drop(rx);
+    rooms.prune_spectators(code.as_str());
+    log::info!("spectator left room {code}");
+}
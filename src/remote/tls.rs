@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{
+    Result,
+    Context,
+    anyhow,
+};
+use ring::digest;
+use rustls::{
+    ClientConfig,
+    DigitallySignedStruct,
+    ServerConfig,
+    SignatureScheme,
+    client::danger::{
+        HandshakeSignatureValid,
+        ServerCertVerified,
+        ServerCertVerifier,
+    },
+    crypto::WebPkiSupportedAlgorithms,
+    pki_types::{
+        CertificateDer,
+        PrivateKeyDer,
+        PrivatePkcs8KeyDer,
+        ServerName,
+        UnixTime,
+    },
+};
+use tokio::{
+    io::{
+        AsyncRead,
+        AsyncWrite,
+    },
+    net::TcpStream,
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    TlsConnector,
+};
+
+use crate::project_dirs;
+
+/// Directory in the config dir that holds our certificate
+const TLS_DIR: &str = "tls";
+const CERT_FILENAME: &str = "cert.der";
+const KEY_FILENAME: &str = "key.der";
+
+/// Anything we can run a websocket over, whether it is encrypted or not
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+pub type BoxedTransport = Box<dyn Transport>;
+
+/// SHA-256 of a certificate. Both ends of a connection mix this into the pairing handshake,
+/// so that the pairing code can only be answered by someone who sees the same certificate we do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint(Vec<u8>);
+impl Fingerprint {
+    pub fn of(cert: &CertificateDer<'_>) -> Fingerprint {
+        Fingerprint(digest::digest(&digest::SHA256, cert.as_ref()).as_ref().to_vec())
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex: Vec<String> = self.0.iter().map(|b| format!("{b:02X}")).collect();
+        write!(f, "{}", hex.join(":"))
+    }
+}
+
+/// Terminates TLS for a listening host, using the self-signed certificate from the config dir
+#[derive(Clone)]
+pub struct HostTls {
+    acceptor: TlsAcceptor,
+    pub fingerprint: Fingerprint,
+}
+impl HostTls {
+    /// Loads our certificate, generating one the first time around
+    pub fn load_or_generate() -> Result<HostTls> {
+        let dir = tls_dir();
+        let (cert, key) = load_or_generate_cert(dir.as_path())?;
+        let fingerprint = Fingerprint::of(&cert);
+
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .context("building TLS config from our certificate")?;
+
+        Ok(HostTls {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            fingerprint,
+        })
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> Result<BoxedTransport> {
+        let stream = self.acceptor.accept(stream).await
+            .context("TLS handshake failed")?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// Starts TLS with a remote host, returning the stream along with the fingerprint of their certificate.
+/// Hosts use self-signed certificates, so the certificate is not checked here.
+/// Instead, the fingerprint is checked by the pairing handshake.
+pub async fn connect(domain: &str, stream: TcpStream) -> Result<(BoxedTransport, Fingerprint)> {
+    let config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(SelfSignedVerifier::default()))
+        .with_no_client_auth();
+
+    let server_name = ServerName::try_from(domain.to_owned())
+        .with_context(|| format!("{domain} is not a valid server name"))?;
+
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream).await
+        .context("TLS handshake failed")?;
+
+    let cert = stream.get_ref().1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .ok_or_else(|| anyhow!("remote did not present a certificate"))?;
+    let fingerprint = Fingerprint::of(cert);
+
+    Ok((Box::new(stream), fingerprint))
+}
+
+fn tls_dir() -> PathBuf {
+    project_dirs()
+        .map(|p| p.config_dir().to_path_buf())
+        .unwrap_or(Path::new(".").to_path_buf())
+        .join(TLS_DIR)
+}
+
+fn load_or_generate_cert(dir: &Path) -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
+    let cert_path = dir.join(CERT_FILENAME);
+    let key_path = dir.join(KEY_FILENAME);
+
+    if cert_path.exists() && key_path.exists() {
+        log::info!("reading TLS certificate from {}", cert_path.display());
+        let cert = fs::read(&cert_path)
+            .with_context(|| format!("reading certificate at {}", cert_path.display()))?;
+        let key = fs::read(&key_path)
+            .with_context(|| format!("reading private key at {}", key_path.display()))?;
+        return Ok((CertificateDer::from(cert), PrivatePkcs8KeyDer::from(key).into()));
+    }
+
+    log::info!("generating a self-signed TLS certificate into {}", dir.display());
+    let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()])
+        .context("generating self-signed certificate")?;
+    let cert = generated.serialize_der()
+        .context("serializing self-signed certificate")?;
+    let key = generated.serialize_private_key_der();
+
+    fs::create_dir_all(dir)
+        .with_context(|| format!("creating {}", dir.display()))?;
+    fs::write(&cert_path, cert.as_slice())
+        .with_context(|| format!("writing certificate to {}", cert_path.display()))?;
+    fs::write(&key_path, key.as_slice())
+        .with_context(|| format!("writing private key to {}", key_path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("restricting permissions on {}", key_path.display()))?;
+    }
+
+    Ok((CertificateDer::from(cert), PrivatePkcs8KeyDer::from(key).into()))
+}
+
+/// Accepts any certificate, but still makes sure the remote holds the key for it
+#[derive(Debug)]
+struct SelfSignedVerifier {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+impl Default for SelfSignedVerifier {
+    fn default() -> Self {
+        SelfSignedVerifier {
+            algorithms: rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        }
+    }
+}
+impl ServerCertVerifier for SelfSignedVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -1,12 +1,35 @@
 use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
 
 use crate::input::{LaneHit, RawLaneHit};
 
 use crate::team_markers::{
     PlayerMarker,
-    EnemyMarker
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+    Team,
 };
 use crate::song::{LoadChartRequest, SyncSpawnerEvent};
+use crate::attacks::{
+    AttackEvent,
+    RawAttackEvent,
+};
+use crate::coop::{
+    CoopMatch,
+    PartnerEventWriters,
+};
+use crate::chat::{
+    ChatEvent,
+    EmoteEvent,
+    RawChatEvent,
+    RawEmoteEvent,
+};
+use crate::series::{
+    RawRoundScoreEvent,
+    RawSeriesStateEvent,
+};
 
 use super::{
     communicate::Comms,
@@ -14,18 +37,103 @@ use super::{
 };
 use crate::judgement::{
     CorrectHitEvent,
+    DroppedNoteEvent,
     RawCorrectHitEvent
 };
-use crate::input::RemoteLaneHit;
+
+/// Everything a GameMessage can turn into, for one team
+#[derive(SystemParam)]
+pub struct TeamEventWriters<'w, T: Marker> {
+    lane_hit: EventWriter<'w, RawLaneHit<T>>,
+    load_chart: EventWriter<'w, LoadChartRequest<T>>,
+    correct_hit: EventWriter<'w, RawCorrectHitEvent<T>>,
+    sync_state: EventWriter<'w, SyncSpawnerEvent<T>>,
+    attack: EventWriter<'w, RawAttackEvent<T>>,
+    series_state: EventWriter<'w, RawSeriesStateEvent<T>>,
+    round_score: EventWriter<'w, RawRoundScoreEvent<T>>,
+    chat: EventWriter<'w, RawChatEvent<T>>,
+    emote: EventWriter<'w, RawEmoteEvent<T>>,
+}
+impl <T: Marker> TeamEventWriters<'_, T> {
+    fn emit(&mut self, msg: GameMessage, now: f32) {
+        let team = T::as_str();
+
+        use GameMessage::*;
+        match msg {
+            LaneHit { lane, beat } => {
+                log::debug!("emitting {team} lane hit");
+                self.lane_hit.send(RawLaneHit::from(
+                    lane,
+                    beat,
+                    now
+                ));
+            }
+            LoadChart { chart_name } => {
+                log::debug!("emitting {team} chart load");
+                self.load_chart.send(LoadChartRequest::from(
+                    chart_name
+                ));
+            }
+            CorrectHit(ev) => {
+                if !T::is_remote() {
+                    // a spectator judges the player panel's lane hits locally, so this would count twice
+                    log::debug!("skipping {team} correct hit, it is judged locally");
+                    return;
+                }
+                log::debug!("emitting {team} correct hit");
+                self.correct_hit.send(RawCorrectHitEvent {
+                    lane_hit: RawLaneHit::from(
+                        ev.lane_hit.lane(),
+                        ev.lane_hit.beat(),
+                        ev.lane_hit.time_of_hit,
+                    ),
+                    arrow_pos: ev.arrow_pos,
+                    grade: ev.grade,
+                });
+            }
+            SyncSpawnerState(ev) => {
+                log::debug!("emitting {team} sync state");
+                self.sync_state.send(ev.for_team());
+            }
+            Attack { attack } => {
+                log::debug!("emitting {team} attack");
+                self.attack.send(RawAttackEvent::from(attack));
+            }
+            SeriesState(state) => {
+                log::debug!("emitting {team} series state");
+                self.series_state.send(RawSeriesStateEvent::from(state));
+            }
+            RoundScore { round, score } => {
+                log::debug!("emitting {team} round score");
+                self.round_score.send(RawRoundScoreEvent::from(round, score));
+            }
+            Chat { text } => {
+                log::debug!("emitting {team} chat");
+                self.chat.send(RawChatEvent::from(text.as_str()));
+            }
+            Emote { emote } => {
+                log::debug!("emitting {team} emote");
+                self.emote.send(RawEmoteEvent::from(emote));
+            }
+            CoopHalf { .. } | DroppedNote { .. } => {
+                log::warn!("dropping {team} co-op message, this is not a co-op match");
+            }
+            Spectated { .. } | Relayed { .. } => {
+                log::warn!("dropping {team} message nested inside another, only the host or relay should wrap these");
+            }
+        }
+    }
+}
 
 /// GameMessages from remote become local game events
 pub fn translate_messages_from_remote(
     time: Res<Time>,
     mut listener: ResMut<Comms>,
-    mut remote_lane_hit: EventWriter<RemoteLaneHit>,
-    mut remote_load_chart: EventWriter<LoadChartRequest<EnemyMarker>>,
-    mut remote_correct_hit: EventWriter<RawCorrectHitEvent<EnemyMarker>>,
-    mut remote_sync_state: EventWriter<SyncSpawnerEvent<EnemyMarker>>,
+    mut remote_events: TeamEventWriters<EnemyMarker>,
+    mut remote2_events: TeamEventWriters<Enemy2Marker>,
+    mut remote3_events: TeamEventWriters<Enemy3Marker>,
+    mut spectated_player_events: TeamEventWriters<PlayerMarker>,
+    mut partner_events: PartnerEventWriters,
 ) {
     let Some(msg) = listener.try_recv_message() else {
         return; // nothing to do
@@ -33,41 +141,41 @@ pub fn translate_messages_from_remote(
 
     let now = time.elapsed().as_secs_f32();
 
-    use GameMessage::*;
-    match msg {
-        LaneHit { lane, beat } => {
-            log::debug!("emitting remote lane hit");
-            remote_lane_hit.send(RemoteLaneHit::from(
-                lane,
-                beat,
-                now
-            ));
-        }
-        LoadChart { chart_name } => {
-            log::debug!("emitting remote chart load");
-            remote_load_chart.send(LoadChartRequest::from(
-                chart_name
-            ));
-        }
-        CorrectHit(ev) => {
-            log::debug!("emitting remote correct hit");
-            remote_correct_hit.send(ev);
-        }
-        SyncSpawnerState(ev) => {
-            log::debug!("emitting remote correct hit");
-            remote_sync_state.send(ev);
+    let (seat, msg) = match msg {
+        // spectators see the host on the player panel, and the host's remote user on the enemy panel
+        GameMessage::Spectated { seat, message } => (seat, *message),
+        // in a relay room of three or more, the relay tells us whose message this is
+        GameMessage::Relayed { seat, message } => (seat, *message),
+        msg => (Team::Enemy, msg),
+    };
+
+    match seat {
+        Team::Player => spectated_player_events.emit(msg, now),
+        Team::Enemy => {
+            // in a co-op match, the remote user is playing on our panel rather than against us
+            if let Some(msg) = partner_events.take(msg) {
+                remote_events.emit(msg, now);
+            }
         }
+        Team::Enemy2 => remote2_events.emit(msg, now),
+        Team::Enemy3 => remote3_events.emit(msg, now),
     }
 }
 
 /// Local GameEvents become GameMessages which are sent to the remote
 pub fn translate_events_from_local(
     mut comms: ResMut<Comms>,
+    coop: Option<Res<CoopMatch>>,
     mut lane_hit_ev: EventReader<LaneHit>,
     mut load_chart_ev: EventReader<LoadChartRequest<PlayerMarker>>,
     mut correct_hit_ev: EventReader<CorrectHitEvent>,
+    mut attack_ev: EventReader<AttackEvent>,
+    mut dropped_note_ev: EventReader<DroppedNoteEvent>,
 ) {
-    for ev in lane_hit_ev.read() {
+    // in a co-op match, each side only tells the other about its own lanes
+    let is_ours = |lane| coop.as_ref().is_none_or(|coop| coop.owns(lane));
+
+    for ev in lane_hit_ev.read().filter(|ev| is_ours(ev.lane())) {
         log::debug!("consuming local lane hit, passing to remote");
         comms.try_send_message(GameMessage::LaneHit {
             lane: ev.lane(),
@@ -80,7 +188,7 @@ pub fn translate_events_from_local(
             chart_name: ev.chart_name().clone(),
         });
     }
-    for ev in correct_hit_ev.read() {
+    for ev in correct_hit_ev.read().filter(|ev| is_ours(ev.lane_hit.lane())) {
         log::debug!("consuming local correct hit, passing to remote");
         comms.try_send_message(GameMessage::CorrectHit(RawCorrectHitEvent {
             lane_hit: RawLaneHit {
@@ -93,6 +201,40 @@ pub fn translate_events_from_local(
             grade: ev.grade,
         }));
     }
+    for ev in attack_ev.read() {
+        log::debug!("consuming local attack, passing to remote");
+        comms.try_send_message(GameMessage::Attack {
+            attack: ev.attack,
+        });
+    }
+    // only a co-op partner needs our dropped notes, an opponent's panel drops them by itself
+    for ev in dropped_note_ev.read().filter(|_| coop.is_some()).filter(|ev| is_ours(ev.arrow().lane())) {
+        log::debug!("consuming local dropped note, passing to partner");
+        comms.try_send_message(GameMessage::DroppedNote {
+            lane: ev.arrow().lane(),
+            arrival_beat: ev.arrow().arrival_beat(),
+        });
+    }
+}
+
+/// Local chat and emotes become GameMessages which are sent to the remote
+pub fn translate_chat_from_local(
+    mut comms: ResMut<Comms>,
+    mut chat_ev: EventReader<ChatEvent>,
+    mut emote_ev: EventReader<EmoteEvent>,
+) {
+    for ev in chat_ev.read() {
+        log::debug!("consuming local chat, passing to remote");
+        comms.try_send_message(GameMessage::Chat {
+            text: ev.text.clone(),
+        });
+    }
+    for ev in emote_ev.read() {
+        log::debug!("consuming local emote, passing to remote");
+        comms.try_send_message(GameMessage::Emote {
+            emote: ev.emote,
+        });
+    }
 }
 
 
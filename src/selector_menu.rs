@@ -4,11 +4,16 @@ use crate::team_markers::{
     PlayerMarker,
     Marker
 };
+use crate::remote::not_spectating;
+use crate::series::may_pick_chart;
+use crate::coop::picks_charts;
+use crate::editor::is_editing;
 use crate::song::{
     ChartAssets,
     ChartName,
     LoadChartRequest,
     SongFinishedEvent,
+    SongState,
 };
 
 #[derive(Debug)]
@@ -67,11 +72,23 @@ fn enable_chart_selector_on_song_end<T: Marker>(
 
 
 
+/// The song may have been picked by someone else, i.e. the remote user in a series
+fn disable_chart_selector_on_song_start(
+    mut state: ResMut<NextState<ChartSelectorState>>,
+) {
+    state.set(ChartSelectorState::Disabled);
+}
+
 fn setup_chart_selector<T: Marker>(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     charts: Res<ChartAssets>,
+    existing: Query<(), With<ChartSelector>>,
 ) {
+    if !existing.is_empty() {
+        return; // already showing
+    }
+
     let font = asset_server.load(crate::BASE_FONT_NAME);
 
     let selectable = charts.chart_names()
@@ -173,8 +190,9 @@ fn interact_with_buttons(
     mut state: ResMut<NextState<ChartSelectorState>>,
     mut load_chart_ev: EventWriter<LoadChartRequest<PlayerMarker>>,
 ) {
-    let mut chart_selector = chart_selector.single_mut(); // otherwise, this system would have nothing
-                                                          // to do
+    let Ok(mut chart_selector) = chart_selector.get_single_mut() else {
+        return; // not showing, i.e. it is not our turn to pick
+    };
 
     let mut do_load_chart = false;
 
@@ -219,13 +237,20 @@ impl Plugin for ChartSelectorPlugin {
         app
             .insert_state(SelectingChart)
 
-            .add_systems(OnEnter(SelectingChart), setup_chart_selector::<PlayerMarker>)
+            // spectators never get to pick the chart, and in a series we take turns,
+            // so the selector comes and goes as the turn changes
             .add_systems(Update, (
-                interact_with_buttons
-            ).run_if(selecting))
+                setup_chart_selector::<PlayerMarker>,
+                interact_with_buttons,
+            ).run_if(selecting.clone()).run_if(not_spectating).run_if(may_pick_chart).run_if(picks_charts).run_if(not(is_editing)))
+            .add_systems(Update, despawn_chart_selector::<PlayerMarker>
+                .run_if(selecting)
+                .run_if(not(may_pick_chart).or_else(not(picks_charts)))
+            )
+            .add_systems(OnEnter(SongState::SettingUp::<PlayerMarker>), disable_chart_selector_on_song_start)
             .add_systems(OnExit(SelectingChart), despawn_chart_selector::<PlayerMarker>)
             .add_systems(Update, 
-                enable_chart_selector_on_song_end::<PlayerMarker>
+                enable_chart_selector_on_song_end::<PlayerMarker>.run_if(not_spectating)
             )
         ;
     }
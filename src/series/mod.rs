@@ -0,0 +1,392 @@
+use anyhow::Result;
+use bevy::prelude::*;
+use serde::{
+    Deserialize,
+    Serialize
+};
+
+mod scoreboard;
+
+use crate::{
+    CliArgs,
+    ConnectionMode,
+};
+use crate::judgement::SongMetrics;
+use crate::remote::{
+    communicate::Comms,
+    not_spectating,
+    translate,
+    GameMessage,
+};
+use crate::song::{
+    ChartName,
+    LoadChartRequest,
+    SongFinishedEvent,
+    SongState,
+};
+use crate::team_markers::{
+    PlayerMarker,
+    EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
+    Marker,
+    Team,
+};
+
+/// A series needs an odd number of rounds, so that somebody always wins
+pub fn parse_best_of(source: &str) -> Result<u32> {
+    let best_of: u32 = source.trim().parse()?;
+    if best_of == 0 || best_of.is_multiple_of(2) {
+        anyhow::bail!("a series must be best of an odd number of rounds, e.g. 3 or 5, got {best_of}");
+    }
+    Ok(best_of)
+}
+
+/// A match of several songs. Whoever wins the majority of `best_of` rounds wins the series.
+#[derive(Resource)]
+#[derive(Debug, Clone)]
+pub struct Series {
+    best_of: u32,
+    /// Which round is being played, starting at 1
+    round: u32,
+    player_wins: u32,
+    enemy_wins: u32,
+    /// Who picks the chart this round. Both players play the chart they pick.
+    picker: Team,
+    /// Set once we start playing this round's chart, so that we only report scores for songs we played
+    round_in_progress: bool,
+    /// Final scores for this round, as they come in
+    player_score: Option<u32>,
+    enemy_score: Option<u32>,
+    /// Who won the last series, once there has been one
+    last_winner: Option<Team>,
+    /// The remote user's pick for this round, waiting for our last song to end
+    pending_pick: Option<ChartName>,
+}
+impl Series {
+    pub fn new(best_of: u32, first_picker: Team) -> Series {
+        Series {
+            best_of,
+            round: 1,
+            player_wins: 0,
+            enemy_wins: 0,
+            picker: first_picker,
+            round_in_progress: false,
+            player_score: None,
+            enemy_score: None,
+            last_winner: None,
+            pending_pick: None,
+        }
+    }
+    /// Rounds needed to win the series
+    pub fn wins_needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+    pub fn picker(&self) -> Team {
+        self.picker
+    }
+    /// Our view of the series, as the remote user should see it
+    fn to_state(&self) -> SeriesState {
+        SeriesState {
+            best_of: self.best_of,
+            round: self.round,
+            sender_wins: self.player_wins,
+            receiver_wins: self.enemy_wins,
+            sender_picks: self.picker == Team::Player,
+        }
+    }
+    /// Takes on the remote user's view of the series
+    fn load_remote_state(&mut self, state: &SeriesState) {
+        if state.round != self.round {
+            // whatever scores we had were for another round
+            self.player_score = None;
+            self.enemy_score = None;
+            self.round_in_progress = false;
+            self.pending_pick = None;
+        }
+        self.best_of = state.best_of;
+        self.round = state.round;
+        self.player_wins = state.receiver_wins;
+        self.enemy_wins = state.sender_wins;
+        self.picker = if state.sender_picks { Team::Enemy } else { Team::Player };
+    }
+    /// Once both scores are in, decides the round and moves on to the next one
+    fn try_finish_round(&mut self) -> bool {
+        let (Some(player_score), Some(enemy_score)) = (self.player_score, self.enemy_score) else {
+            return false;
+        };
+
+        self.player_score = None;
+        self.enemy_score = None;
+        self.round_in_progress = false;
+
+        // a tie is not a win for anyone, so the round is played over, picked by the same user
+        if player_score == enemy_score {
+            log::info!("round {} tied at {player_score}, playing it over", self.round);
+            return true;
+        }
+        if player_score > enemy_score {
+            self.player_wins += 1;
+        } else {
+            self.enemy_wins += 1;
+        }
+        log::info!("round {} finished, {player_score} to {enemy_score}. Series is {} to {}", self.round, self.player_wins, self.enemy_wins);
+
+        self.round += 1;
+        self.picker = match self.picker {
+            Team::Player => Team::Enemy,
+            _ => Team::Player,
+        };
+
+        let winner = if self.player_wins >= self.wins_needed() {
+            Some(Team::Player)
+        } else if self.enemy_wins >= self.wins_needed() {
+            Some(Team::Enemy)
+        } else {
+            None
+        };
+        if let Some(winner) = winner {
+            log::info!("series won by {winner:?}, starting a new one");
+            let mut next = Series::new(self.best_of, self.picker);
+            next.last_winner = Some(winner);
+            *self = next;
+        }
+
+        true
+    }
+}
+
+/// The sender's view of the series. Sent along with each pick, and after each round.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SeriesState {
+    best_of: u32,
+    round: u32,
+    sender_wins: u32,
+    receiver_wins: u32,
+    /// Whether the sender picks the chart this round
+    sender_picks: bool,
+}
+
+/// Team `T` sent their view of the series
+#[derive(Event)]
+#[derive(Debug,Clone)]
+pub struct RawSeriesStateEvent<T: Marker> {
+    pub state: SeriesState,
+    pub _team: T,
+}
+impl <T: Marker> RawSeriesStateEvent<T> {
+    pub fn from(state: SeriesState) -> RawSeriesStateEvent<T> {
+        Self {
+            state,
+            _team: T::marker(),
+        }
+    }
+}
+
+/// Team `T` finished a round of the series with this score
+#[derive(Event)]
+#[derive(Debug,Clone)]
+pub struct RawRoundScoreEvent<T: Marker> {
+    pub round: u32,
+    pub score: u32,
+    pub _team: T,
+}
+impl <T: Marker> RawRoundScoreEvent<T> {
+    pub fn from(round: u32, score: u32) -> RawRoundScoreEvent<T> {
+        Self {
+            round,
+            score,
+            _team: T::marker(),
+        }
+    }
+}
+
+/// Run condition for the chart selector. Outside of a series, anyone may pick.
+/// In a series, the picker waits for the round to be decided, so that both users move on to the next chart together.
+pub fn may_pick_chart(series: Option<Res<Series>>) -> bool {
+    series.is_none_or(|series| series.picker() == Team::Player && !series.round_in_progress)
+}
+
+fn start_series_from_cli(
+    mut commands: Commands,
+    cli: Res<CliArgs>,
+) {
+    let Some(best_of) = cli.best_of else {
+        return; // just playing single songs
+    };
+    if cli.players > 2 {
+        log::warn!("a series is only scored against the first remote user, the others just play along");
+    }
+    // only one side may pick first, or both would pick round 1 at once. The listening host picks,
+    // and in a relay room, where there is no host, whoever asked for the series picks
+    let first_picker = match cli.mode {
+        ConnectionMode::Connect { .. } => Team::Enemy,
+        _ => Team::Player,
+    };
+    match first_picker {
+        Team::Player => log::info!("starting a best of {best_of} series, you pick first"),
+        _ => log::info!("starting a best of {best_of} series, waiting for the host to pick first"),
+    }
+    commands.insert_resource(Series::new(best_of, first_picker));
+}
+
+/// Sends our view of the series before the chart we picked, so the remote knows to follow along
+fn send_series_state_on_pick(
+    mut comms: ResMut<Comms>,
+    series: Option<Res<Series>>,
+    mut load_chart_ev: EventReader<LoadChartRequest<PlayerMarker>>,
+) {
+    let Some(series) = series else {
+        load_chart_ev.clear();
+        return;
+    };
+    for _ in load_chart_ev.read() {
+        if series.picker() != Team::Player {
+            continue; // we are following their pick
+        }
+        comms.try_send_message(GameMessage::SeriesState(series.to_state()));
+    }
+}
+
+fn receive_series_state(
+    mut commands: Commands,
+    series: Option<ResMut<Series>>,
+    mut series_ev: EventReader<RawSeriesStateEvent<EnemyMarker>>,
+) {
+    let Some(ev) = series_ev.read().last() else {
+        return;
+    };
+    match series {
+        Some(mut series) => {
+            if ev.state.round == 1 && ev.state.sender_picks && series.round == 1 && series.picker() == Team::Player && !series.round_in_progress {
+                log::warn!("remote user also picked the first chart of the series, only one of you should give --best-of in a relay room. Following their pick");
+            }
+            series.load_remote_state(&ev.state)
+        }
+        None => {
+            log::info!("remote user started a best of {} series", ev.state.best_of);
+            let mut series = Series::new(ev.state.best_of, Team::Enemy);
+            series.load_remote_state(&ev.state);
+            commands.insert_resource(series);
+        }
+    }
+}
+
+/// When it is the remote user's pick, we play the chart they picked, once our own song is over
+fn follow_remote_pick(
+    series: Option<ResMut<Series>>,
+    song_state: Res<State<SongState<PlayerMarker>>>,
+    mut remote_load_chart_ev: EventReader<LoadChartRequest<EnemyMarker>>,
+    mut load_chart_ev: EventWriter<LoadChartRequest<PlayerMarker>>,
+) {
+    let Some(mut series) = series else {
+        remote_load_chart_ev.clear();
+        return;
+    };
+    for ev in remote_load_chart_ev.read() {
+        if series.picker() != Team::Enemy {
+            log::warn!("ignoring remote user's pick of {}, it is our pick", ev.chart_name());
+            continue;
+        }
+        series.pending_pick = Some(ev.chart_name().clone());
+    }
+
+    if !matches!(song_state.get(), SongState::NotPlaying) {
+        return; // still playing the last one
+    }
+    if let Some(chart_name) = series.pending_pick.take() {
+        log::info!("following remote user's pick of {chart_name}");
+        load_chart_ev.send(LoadChartRequest::from(chart_name));
+    }
+}
+
+fn mark_round_in_progress(
+    series: Option<ResMut<Series>>,
+) {
+    if let Some(mut series) = series {
+        series.round_in_progress = true;
+    }
+}
+
+/// Reports our final score to the remote user when our song ends
+fn report_round_score(
+    mut comms: ResMut<Comms>,
+    series: Option<ResMut<Series>>,
+    metrics: Res<SongMetrics>,
+    mut song_end_ev: EventReader<SongFinishedEvent<PlayerMarker>>,
+) {
+    if song_end_ev.is_empty() {
+        return;
+    }
+    song_end_ev.clear();
+
+    let Some(mut series) = series else {
+        return;
+    };
+    if !series.round_in_progress {
+        return; // the song ended without us playing a round, i.e. on start up
+    }
+
+    let score = metrics.score();
+    series.player_score = Some(score);
+    comms.try_send_message(GameMessage::RoundScore {
+        round: series.round,
+        score,
+    });
+
+    if series.try_finish_round() {
+        comms.try_send_message(GameMessage::SeriesState(series.to_state()));
+    }
+}
+
+fn receive_round_score(
+    mut comms: ResMut<Comms>,
+    series: Option<ResMut<Series>>,
+    mut round_score_ev: EventReader<RawRoundScoreEvent<EnemyMarker>>,
+) {
+    let Some(mut series) = series else {
+        round_score_ev.clear();
+        return;
+    };
+    for ev in round_score_ev.read() {
+        if ev.round != series.round {
+            log::warn!("ignoring remote score for round {}, we are on round {}", ev.round, series.round);
+            continue;
+        }
+        series.enemy_score = Some(ev.score);
+
+        if series.try_finish_round() {
+            comms.try_send_message(GameMessage::SeriesState(series.to_state()));
+        }
+    }
+}
+
+pub struct SeriesPlugin;
+impl Plugin for SeriesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<RawSeriesStateEvent<PlayerMarker>>()
+            .add_event::<RawSeriesStateEvent<EnemyMarker>>()
+            .add_event::<RawRoundScoreEvent<PlayerMarker>>()
+            .add_event::<RawRoundScoreEvent<EnemyMarker>>()
+            // series are only played between two users, but anyone in the room may still send these
+            .add_event::<RawSeriesStateEvent<Enemy2Marker>>()
+            .add_event::<RawSeriesStateEvent<Enemy3Marker>>()
+            .add_event::<RawRoundScoreEvent<Enemy2Marker>>()
+            .add_event::<RawRoundScoreEvent<Enemy3Marker>>()
+
+            .add_systems(Startup, start_series_from_cli)
+            .add_systems(OnEnter(SongState::Playing::<PlayerMarker>), mark_round_in_progress)
+            .add_systems(Update, (
+                    send_series_state_on_pick
+                        .before(translate::translate_events_from_local),
+                    receive_series_state,
+                    follow_remote_pick
+                        .after(receive_series_state),
+                    report_round_score,
+                    receive_round_score,
+            ).run_if(not_spectating))
+            .add_plugins(scoreboard::ScoreboardPlugin)
+        ;
+    }
+}
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+
+use crate::song::SongState;
+use crate::team_markers::{
+    PlayerMarker,
+    Team,
+};
+
+use super::Series;
+
+const SCOREBOARD_TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const SCOREBOARD_FONT_SIZE: f32 = 40.0;
+
+/// Shows how the series is going, in between songs
+#[derive(Component)]
+struct Scoreboard;
+
+fn setup_scoreboard(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(crate::BASE_FONT_NAME);
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(10.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Scoreboard,
+                TextBundle {
+                    text: Text::from_section(
+                        "".to_string(),
+                        TextStyle {
+                            font,
+                            font_size: SCOREBOARD_FONT_SIZE,
+                            color: SCOREBOARD_TEXT_COLOR,
+                        },
+                    ).with_justify(JustifyText::Center),
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn scoreboard_content(series: &Series) -> String {
+    let mut content = String::new();
+
+    match series.last_winner {
+        Some(Team::Player) => content.push_str("You won the last series!\n"),
+        Some(_) => content.push_str("They won the last series!\n"),
+        None => {}
+    }
+
+    content.push_str(format!(
+        "Best of {} - round {}\nYou {} : {} Them\n",
+        series.best_of, series.round, series.player_wins, series.enemy_wins
+    ).as_str());
+
+    match series.picker() {
+        Team::Player => content.push_str("Your pick"),
+        _ => content.push_str("Waiting for their pick"),
+    }
+
+    content
+}
+
+/// Keeps the scoreboard up to date, and only shows it while we are between songs
+fn update_scoreboard(
+    series: Option<Res<Series>>,
+    song_state: Res<State<SongState<PlayerMarker>>>,
+    mut text_q: Query<&mut Text, With<Scoreboard>>,
+) {
+    let between_songs = matches!(song_state.get(), SongState::NotPlaying);
+
+    let content = match series {
+        Some(series) if between_songs => scoreboard_content(series.as_ref()),
+        _ => String::new(),
+    };
+
+    for mut text in text_q.iter_mut() {
+        if text.sections[0].value != content {
+            text.sections[0].value = content.clone();
+        }
+    }
+}
+
+pub struct ScoreboardPlugin;
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, setup_scoreboard)
+            .add_systems(Update, update_scoreboard)
+        ;
+    }
+}
@@ -12,11 +12,20 @@ use serde::{
 };
 
 use crate::lane::Lane;
+use super::migrate::{
+    self,
+    CURRENT_FORMAT_VERSION,
+};
 
 #[derive(Reflect)]
 #[derive(Serialize, Deserialize)]
 #[derive(Debug, Clone)]
 struct ChartData {
+    /// Which version of the chart format the file is in. Files from before there were versions are version 0,
+    /// and are upgraded as they load
+    #[serde(default)]
+    format_version: u32,
+
     /// The user friendly name of the chart
     chart_name: String,
 
@@ -38,6 +47,54 @@ struct ChartData {
 
     /// The song file name in assets/songs folder
     sound_file: Option<String>,
+
+    /// How many beats of the chart make up one beat of the song, for charts with notes in between the song's beats.
+    /// Defaults to one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rows_per_beat: Option<u32>,
+}
+
+/// Every key of a chart file that the game reads. Anything else in the file is ignored
+pub(super) fn chart_keys() -> &'static [&'static str] {
+    field_names::<ChartData>()
+}
+
+/// Every key of a note that the game reads
+pub(super) fn note_keys() -> &'static [&'static str] {
+    field_names::<Note>()
+}
+
+/// The keys a struct's derived `Deserialize` reads, so they never drift from the struct itself.
+/// The derive hands them to `deserialize_struct`, which is the only thing this deserializer answers
+fn field_names<T: serde::de::DeserializeOwned>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+    impl<'de> serde::Deserializer<'de> for FieldNames<'_> {
+        type Error = serde::de::value::Error;
+
+        fn deserialize_any<V: serde::de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(serde::de::Error::custom("only structs have field names"))
+        }
+        fn deserialize_struct<V: serde::de::Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(serde::de::Error::custom("stopping once the field names are known"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    // always an error, the field names are all that is wanted
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -70,6 +127,7 @@ impl Chart {
     pub fn empty() -> Chart {
         Chart {
             data: ChartData {
+                format_version: CURRENT_FORMAT_VERSION,
                 chart_name: "".to_string(),
                 description: None,
                 beat_duration_secs: 0.0,
@@ -77,10 +135,46 @@ impl Chart {
                 song_end_beats: None,
                 beats: Vec::new(),
                 sound_file: None,
+                rows_per_beat: None,
             },
             name: ChartName { name: "".to_owned() } 
         }
     }
+    /// A chart made in game rather than loaded from the chart directory, e.g. while recording
+    pub fn new(name: ChartName, description: Option<String>, beat_duration_secs: f32, lead_time_beats: f32, sound_file: Option<String>, beats: Vec<Vec<Note>>) -> Chart {
+        Chart {
+            data: ChartData {
+                format_version: CURRENT_FORMAT_VERSION,
+                chart_name: name.name.clone(),
+                description,
+                beat_duration_secs,
+                lead_time_beats,
+                song_end_beats: None,
+                beats,
+                sound_file,
+                rows_per_beat: None,
+            },
+            name,
+        }
+    }
+    /// Marks how many beats of the chart make up one beat of the song
+    pub fn with_rows_per_beat(mut self, rows_per_beat: u32) -> Chart {
+        self.data.rows_per_beat = Some(rows_per_beat).filter(|&rows| rows > 1);
+        self
+    }
+    /// The same chart, but with different notes
+    pub fn with_beats(&self, beats: Vec<Vec<Note>>) -> Chart {
+        let mut chart = self.clone();
+        chart.data.beats = beats;
+        chart
+    }
+    /// The same chart, saved under another name
+    pub fn renamed(&self, name: ChartName) -> Chart {
+        let mut chart = self.clone();
+        chart.data.chart_name = name.name.clone();
+        chart.name = name;
+        chart
+    }
     pub fn try_load_from_name(name: &ChartName) -> Result<Chart> {
         let path = format!("assets/charts/{}.json", name.name);
 
@@ -88,17 +182,50 @@ impl Chart {
         let text = std::fs::read_to_string(&path)
             .with_context(|| format!("reading from file {path}"))?;
 
-        let chart_data: ChartData = serde_json::from_str(text.as_str())
+        let chart = Chart::from_json(name, text.as_str())
             .context("parsing json")?;
 
         log::info!("Parsed chart '{}' from {}", name, path);
 
-        let chart = Chart {
+        Ok(chart)
+    }
+    /// Reads a chart from the text of its file, upgrading it first if it is in an older format
+    pub fn from_json(name: &ChartName, text: &str) -> Result<Chart> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        let chart_data: ChartData = if migrate::format_version(&value) == CURRENT_FORMAT_VERSION {
+            // straight from the text, so that errors say where in it they are
+            serde_json::from_str(text)?
+        } else {
+            serde_json::from_value(migrate::migrate(value)?)?
+        };
+        Ok(Chart {
             data: chart_data,
             name: name.clone()
-        };
-
-        Ok(chart)
+        })
+    }
+    /// Writes the chart to the chart directory, under its own name. Never overwrites an existing chart.
+    pub fn save_new(&self) -> Result<std::path::PathBuf> {
+        if self.name.is_taken() {
+            anyhow::bail!("there is already a chart named {}", self.name);
+        }
+        self.save()
+    }
+    /// Writes the chart to the chart directory, under its own name, replacing whatever was there
+    pub fn save(&self) -> Result<std::path::PathBuf> {
+        let path = std::path::Path::new(CHART_ASSET_PATH)
+            .join(format!("{}.json", self.name.name));
+        self.write_to(&path)?;
+        Ok(path)
+    }
+    /// Writes the chart to any file, replacing whatever was there
+    pub fn write_to(&self, path: &std::path::Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(&self.data)
+            .context("serializing chart")?;
+        std::fs::write(path, text)
+            .with_context(|| format!("writing chart to {}", path.display()))?;
+
+        log::info!("Saved chart '{}' to {}", self.name, path.display());
+        Ok(())
     }
     pub fn chart_name(&self) -> &ChartName {
         &self.name
@@ -124,6 +251,14 @@ impl Chart {
         beats + self.lead_time_beats()
     }
 
+    /// How many beats of the chart make up one beat of the song
+    pub fn rows_per_beat(&self) -> u32 {
+        self.data.rows_per_beat.unwrap_or(1).max(1)
+    }
+    pub fn beats(&self) -> &[Vec<Note>] {
+        self.data.beats.as_slice()
+    }
+
     /// Iterate over all beats in the chart
     pub fn beats_iter(&self) -> impl Iterator<Item = &[Note]> + '_ {
         log::info!("in beats_iter");
@@ -134,6 +269,18 @@ impl Chart {
     }
 }
 
+impl ChartName {
+    pub fn new(name: impl Into<String>) -> ChartName {
+        ChartName { name: name.into() }
+    }
+    /// Whether there is already a chart by this name in the chart directory
+    pub fn is_taken(&self) -> bool {
+        std::path::Path::new(CHART_ASSET_PATH)
+            .join(format!("{}.json", self.name))
+            .exists()
+    }
+}
+
 impl std::fmt::Display for ChartName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.name)
@@ -141,13 +288,16 @@ impl std::fmt::Display for ChartName {
 }
 
 impl Note {
+    pub fn new(lane: Lane) -> Note {
+        Note { lane }
+    }
     pub fn lane(&self) -> Lane {
         self.lane
     }
 }
 
 
-const CHART_ASSET_PATH: &'static str = "assets/charts/";
+pub(super) const CHART_ASSET_PATH: &'static str = "assets/charts/";
 
 #[derive(Debug, Clone, Resource)]
 /// Contains the references for all loaded charts
@@ -204,6 +354,10 @@ impl ChartAssets {
         })
 
     }
+    /// The chart by this name, if it was loaded
+    pub fn try_get(&self, name: &ChartName) -> Option<&Arc<Chart>> {
+        self.mapping.get(name)
+    }
     pub fn get(&self, name: &ChartName) -> &Arc<Chart> {
         self.mapping
             .get(name)
@@ -213,6 +367,11 @@ impl ChartAssets {
             })
     }
 
+    /// Makes a chart that was not in the chart directory on start up available to play
+    pub fn insert(&mut self, chart: Chart) {
+        self.mapping.insert(chart.chart_name().clone(), Arc::new(chart));
+    }
+
     pub fn chart_names(&self) -> impl Iterator<Item = &ChartName> {
         self.mapping.keys()
     }
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+
+use crate::lane::LaneMap;
+use crate::record::SOUND_ASSET_PATH;
+
+use super::migrate::{
+    format_version,
+    migrate,
+    CURRENT_FORMAT_VERSION,
+};
+use super::chart::{
+    Chart,
+    ChartName,
+    chart_keys,
+    note_keys,
+    CHART_ASSET_PATH,
+};
+
+/// Notes closer together than this on one lane are too fast for one finger to keep up with
+const MIN_JACK_SECS: f32 = 0.1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    /// The chart plays, but probably not the way its author meant it to
+    Warning,
+    /// The chart will not load, or is not worth playing
+    Error,
+}
+
+/// One problem with a chart
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Line and column in the chart file, for problems that have one
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+impl Finding {
+    fn error(message: impl Into<String>) -> Finding {
+        Finding { severity: Severity::Error, location: None, message: message.into() }
+    }
+    fn warning(message: impl Into<String>) -> Finding {
+        Finding { severity: Severity::Warning, location: None, message: message.into() }
+    }
+}
+
+/// Everything wrong with one chart file
+#[derive(Debug, Clone)]
+pub struct ChartReport {
+    pub path: PathBuf,
+    pub findings: Vec<Finding>,
+}
+impl ChartReport {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|finding| finding.severity == severity).count()
+    }
+}
+
+/// Every chart file in the chart directory, in order of file name
+pub(super) fn chart_files() -> Result<Vec<PathBuf>> {
+    let dir = std::fs::read_dir(CHART_ASSET_PATH)
+        .with_context(|| format!("while reading chart directory at {CHART_ASSET_PATH}"))?;
+
+    let mut paths = Vec::new();
+    for entry in dir {
+        let path = entry.context("reading entry in chart directory")?.path();
+        if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Checks every chart in the chart directory, in order of file name
+pub fn check_chart_dir() -> Result<Vec<ChartReport>> {
+    Ok(chart_files()?.iter().map(|path| check_chart_file(path)).collect())
+}
+
+/// Checks a chart file, anywhere on disk
+pub fn check_chart_file(path: &Path) -> ChartReport {
+    let findings = match std::fs::read_to_string(path) {
+        Ok(text) => {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+            check_chart_text(&ChartName::new(name), text.as_str())
+        }
+        Err(e) => vec![Finding::error(format!("unable to read file: {e}"))],
+    };
+    ChartReport {
+        path: path.to_path_buf(),
+        findings,
+    }
+}
+
+/// Checks the text of a chart file: first that the game can read it at all, then what it holds
+pub fn check_chart_text(name: &ChartName, text: &str) -> Vec<Finding> {
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return vec![schema_error(&e)],
+    };
+
+    let mut findings = Vec::new();
+    let version = format_version(&value);
+    if version < CURRENT_FORMAT_VERSION {
+        findings.push(Finding::warning(format!(
+            "chart is in format version {version}, run migrate-charts to upgrade it to version {CURRENT_FORMAT_VERSION}"
+        )));
+    }
+
+    // keys that loading upgrades are not unknown, so look at the chart as it will be once upgraded,
+    // but still point out the old keys that the upgrade drops or converts
+    let upgraded = migrate(value.clone()).unwrap_or_else(|_| value.clone());
+    findings.extend(upgraded_keys(&value, &upgraded, version));
+    findings.extend(unknown_keys(&upgraded));
+
+    match Chart::from_json(name, text) {
+        Ok(chart) => findings.extend(check_chart(&chart)),
+        Err(e) => findings.push(match e.downcast_ref::<serde_json::Error>() {
+            Some(e) => schema_error(e),
+            None => Finding::error(format!("{e:#}")),
+        }),
+    }
+    findings
+}
+
+/// Checks a chart that loaded, for things that would make it unplayable or awkward
+pub fn check_chart(chart: &Chart) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let beat_duration_secs = chart.beat_duration_secs();
+    if !(beat_duration_secs > 0.0 && beat_duration_secs.is_finite()) {
+        findings.push(Finding::error(format!("beat_duration_secs must be more than zero, got {beat_duration_secs}")));
+    }
+
+    if let Some(sound_file) = chart.sound_file() {
+        let path = Path::new(SOUND_ASSET_PATH).join(sound_file);
+        if !path.is_file() {
+            findings.push(Finding::error(format!("sound file {sound_file} is missing, expected it at {}", path.display())));
+        }
+    }
+
+    if chart.beats().iter().all(Vec::is_empty) {
+        findings.push(Finding::error("chart has no notes"));
+        return findings;
+    }
+
+    for (row, notes) in chart.beats().iter().enumerate() {
+        let mut seen = LaneMap::<bool>::new();
+        for note in notes {
+            if std::mem::replace(&mut seen[note.lane()], true) {
+                findings.push(Finding::warning(format!("row {row} has {} more than once", note.lane().as_str())));
+            }
+        }
+    }
+
+    findings.extend(fast_jacks(chart));
+    findings
+}
+
+/// One warning for each lane with notes following each other faster than can be played
+fn fast_jacks(chart: &Chart) -> Vec<Finding> {
+    let beat_duration_secs = chart.beat_duration_secs();
+    if beat_duration_secs <= 0.0 {
+        // already an error, and every jack would count
+        return Vec::new();
+    }
+
+    // the row of the last note on each lane, then the first fast jack and how many there are
+    let mut last_row = LaneMap::<Option<usize>>::new();
+    let mut fast = LaneMap::<Option<(usize, usize)>>::new();
+    for (row, notes) in chart.beats().iter().enumerate() {
+        for note in notes {
+            let lane = note.lane();
+            if let Some(last) = last_row[lane].filter(|&last| last < row) {
+                let secs = (row - last) as f32 * beat_duration_secs;
+                if secs < MIN_JACK_SECS {
+                    fast[lane].get_or_insert((row, 0)).1 += 1;
+                }
+            }
+            last_row[lane] = Some(row);
+        }
+    }
+
+    fast.iter()
+        .filter_map(|(lane, fast)| fast.map(|(first, count)| (lane, first, count)))
+        .map(|(lane, first, count)| Finding::warning(format!(
+            "{count} notes on {} follow the one before in under {MIN_JACK_SECS}s, the first at row {first}",
+            lane.as_str(),
+        )))
+        .collect()
+}
+
+/// Keys from an older format, which loading drops or turns into other keys
+fn upgraded_keys(original: &serde_json::Value, upgraded: &serde_json::Value, version: u32) -> Vec<Finding> {
+    let (Some(original), Some(upgraded)) = (original.as_object(), upgraded.as_object()) else {
+        return Vec::new();
+    };
+    original.keys()
+        .filter(|key| !upgraded.contains_key(key.as_str()))
+        .map(|key| Finding::warning(format!(
+            "key {key:?} is from format version {version}, the game upgrades it away when loading. Run migrate-charts to update the file"
+        )))
+        .collect()
+}
+
+/// Keys the game does not read, which usually means a typo or a field from an old version of the game
+fn unknown_keys(value: &serde_json::Value) -> Vec<Finding> {
+    let Some(chart) = value.as_object() else {
+        // the chart will fail to load, which says more than this could
+        return Vec::new();
+    };
+
+    let known_chart_keys = chart_keys();
+    let known_note_keys = note_keys();
+
+    let mut findings: Vec<Finding> = chart.keys()
+        .filter(|key| !known_chart_keys.contains(&key.as_str()))
+        .map(|key| Finding::warning(format!("unknown key {key:?}, the game ignores it")))
+        .collect();
+
+    // how many notes have each unknown key, and the first row with it
+    let mut note_keys: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    let rows = chart.get("beats").and_then(|beats| beats.as_array());
+    for (row, notes) in rows.into_iter().flatten().enumerate() {
+        let notes = notes.as_array().into_iter().flatten();
+        for key in notes.filter_map(|note| note.as_object()).flat_map(|note| note.keys()) {
+            if !known_note_keys.contains(&key.as_str()) {
+                note_keys.entry(key.as_str()).or_insert((row, 0)).1 += 1;
+            }
+        }
+    }
+    findings.extend(note_keys.into_iter().map(|(key, (row, count))| Finding::warning(format!(
+        "unknown key {key:?} on {count} notes, the first at row {row}, the game ignores it"
+    ))));
+
+    findings
+}
+
+fn schema_error(e: &serde_json::Error) -> Finding {
+    // serde puts the location on the end of the message, but it is given separately here
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+    let message = message.strip_suffix(suffix.as_str()).unwrap_or(message.as_str());
+    Finding {
+        severity: Severity::Error,
+        // errors in upgraded charts are not from the text, and have no location
+        location: Some((e.line(), e.column())).filter(|&(line, _)| line > 0),
+        message: message.to_string(),
+    }
+}
+
+/// Which charts to check, as given on the command line
+#[derive(clap::Args)]
+#[derive(Debug, Clone)]
+pub struct CheckArgs {
+    /// Chart files to check. By default, every chart in assets/charts.
+    pub charts: Vec<PathBuf>,
+
+    /// Fail on warnings too, not just errors
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+/// Prints everything wrong with the charts, and fails if any of them have errors. Does not open a window.
+pub fn run_check_charts(args: &CheckArgs) -> Result<()> {
+    let reports = if args.charts.is_empty() {
+        check_chart_dir()?
+    } else {
+        args.charts.iter().map(|path| check_chart_file(path)).collect()
+    };
+
+    for report in reports.iter() {
+        for finding in report.findings.iter() {
+            let severity = match finding.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            match finding.location {
+                Some((line, column)) => println!("{}:{line}:{column}: {severity}: {}", report.path.display(), finding.message),
+                None => println!("{}: {severity}: {}", report.path.display(), finding.message),
+            }
+        }
+    }
+
+    let errors: usize = reports.iter().map(|report| report.count(Severity::Error)).sum();
+    let warnings: usize = reports.iter().map(|report| report.count(Severity::Warning)).sum();
+    println!("checked {} charts: {errors} errors, {warnings} warnings", reports.len());
+
+    if errors > 0 || (args.deny_warnings && warnings > 0) {
+        anyhow::bail!("some charts did not pass");
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{
+    Context,
+    Result,
+};
+use bevy::audio::{
+    AudioSource,
+    Decodable,
+    Source,
+};
+
+/// A whole song, mixed down to one channel
+#[derive(Debug, Clone)]
+pub struct DecodedSong {
+    pub sample_rate: u32,
+    /// From -1 to 1
+    pub samples: Vec<f32>,
+}
+
+/// Averages each frame of interleaved samples down to one
+fn mix_down(interleaved: impl Iterator<Item = f32>, channels: usize) -> Vec<f32> {
+    let channels = channels.max(1);
+    let interleaved: Vec<f32> = interleaved.collect();
+    interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// Decodes the whole song up front, rather than while it plays. Only ogg and wav are supported.
+pub fn decode_sound_file(path: &Path) -> Result<DecodedSong> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("reading song from {}", path.display()))?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("ogg") => decode_ogg(bytes),
+        Some("wav") => decode_wav(bytes.as_slice()),
+        _ => anyhow::bail!("can only decode ogg or wav songs, not {}", path.display()),
+    }
+    .with_context(|| format!("decoding {}", path.display()))
+}
+
+fn decode_ogg(bytes: Vec<u8>) -> Result<DecodedSong> {
+    // the decoder panics on anything it does not recognize, so check first
+    if !bytes.starts_with(b"OggS") {
+        anyhow::bail!("not an ogg file");
+    }
+    let source = AudioSource {
+        bytes: Arc::from(bytes),
+    };
+    let decoder = source.decoder();
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels() as usize;
+    let samples = mix_down(decoder.map(|sample| sample as f32 / i16::MAX as f32), channels);
+    Ok(DecodedSong {
+        sample_rate,
+        samples,
+    })
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The wav format tags we understand. Extensible files give the real tag further in.
+const WAV_PCM: u16 = 1;
+const WAV_FLOAT: u16 = 3;
+const WAV_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Reads plain PCM or float wav files, which is what every audio editor writes out
+fn decode_wav(bytes: &[u8]) -> Result<DecodedSong> {
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        anyhow::bail!("not a wav file");
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut at = 12;
+    while let (Some(id), Some(len)) = (bytes.get(at..at + 4), read_u32(bytes, at + 4)) {
+        let start = at + 8;
+        let end = (start + len as usize).min(bytes.len());
+        match id {
+            b"fmt " => format = Some(&bytes[start..end]),
+            b"data" => data = Some(&bytes[start..end]),
+            _ => {}
+        }
+        // chunks are padded to an even length
+        at = start + len as usize + (len as usize & 1);
+    }
+    let format = format.context("wav file has no fmt chunk")?;
+    let data = data.context("wav file has no data chunk")?;
+
+    let mut tag = read_u16(format, 0).context("fmt chunk is too short")?;
+    let channels = read_u16(format, 2).context("fmt chunk is too short")? as usize;
+    let sample_rate = read_u32(format, 4).context("fmt chunk is too short")?;
+    let bits = read_u16(format, 14).context("fmt chunk is too short")?;
+    if tag == WAV_EXTENSIBLE {
+        tag = read_u16(format, 24).context("extensible fmt chunk is too short")?;
+    }
+
+    let samples: Box<dyn Iterator<Item = f32>> = match (tag, bits) {
+        (WAV_PCM, 8) => Box::new(data.iter().map(|&b| (b as f32 - 128.0) / 128.0)),
+        (WAV_PCM, 16) => Box::new(data
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)),
+        (WAV_PCM, 24) => Box::new(data
+            .chunks_exact(3)
+            .map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / i32::MAX as f32)),
+        (WAV_PCM, 32) => Box::new(data
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / i32::MAX as f32)),
+        (WAV_FLOAT, 32) => Box::new(data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))),
+        _ => anyhow::bail!("unsupported wav format {tag} at {bits} bits"),
+    };
+
+    Ok(DecodedSong {
+        sample_rate,
+        samples: mix_down(samples, channels),
+    })
+}
@@ -0,0 +1,209 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use serde_json::{
+    Map,
+    Value,
+};
+
+use super::chart::{
+    Chart,
+    ChartName,
+};
+use super::check::chart_files;
+
+/// Upgrades the keys of a chart from one format version to the next
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// Each migration upgrades a chart from the version at its index to the one after.
+/// Add new ones to the end, and never change one that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    v0_to_v1,
+];
+
+/// The chart format this version of the game reads and writes
+pub const CURRENT_FORMAT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The format version of chart json. Charts from before there were versions are version 0
+pub(super) fn format_version(value: &Value) -> u32 {
+    value.get("format_version")
+        .and_then(Value::as_u64)
+        .map_or(0, |version| version as u32)
+}
+
+/// Upgrades chart json from whatever version it is to the current one
+pub(super) fn migrate(mut value: Value) -> Result<Value> {
+    let version = format_version(&value);
+    if version > CURRENT_FORMAT_VERSION {
+        anyhow::bail!("chart is in format version {version}, from a newer version of the game. This one reads up to version {CURRENT_FORMAT_VERSION}");
+    }
+    let Some(chart) = value.as_object_mut() else {
+        anyhow::bail!("expected the chart to be a json object");
+    };
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(chart)
+            .with_context(|| format!("upgrading chart from format version {from}"))?;
+        chart.insert("format_version".to_string(), Value::from(from + 1));
+    }
+    Ok(value)
+}
+
+/// Charts made by tools/make_random_map.py gave their lead time in seconds, and the
+/// early charts had a `wait_for_music_end` that was never read
+fn v0_to_v1(chart: &mut Map<String, Value>) -> Result<()> {
+    if let Some(lead_time_secs) = chart.remove("lead_time_secs") {
+        if !chart.contains_key("lead_time_beats") {
+            let lead_time_secs = lead_time_secs.as_f64()
+                .context("expected lead_time_secs to be a number")?;
+            let beat_duration_secs = chart.get("beat_duration_secs")
+                .and_then(Value::as_f64)
+                .filter(|&secs| secs > 0.0)
+                .context("lead_time_secs needs a positive beat_duration_secs to become lead_time_beats")?;
+            chart.insert("lead_time_beats".to_string(), Value::from(lead_time_secs / beat_duration_secs));
+        }
+    }
+    chart.remove("wait_for_music_end");
+    Ok(())
+}
+
+/// Which charts to upgrade, as given on the command line
+#[derive(clap::Args)]
+#[derive(Debug, Clone)]
+pub struct MigrateArgs {
+    /// Chart files to upgrade. By default, every chart in assets/charts.
+    pub charts: Vec<PathBuf>,
+}
+
+/// Rewrites charts in older formats in the current one, keeping the original next to each as a backup.
+/// Does not open a window.
+pub fn run_migrate_charts(args: &MigrateArgs) -> Result<()> {
+    let paths = if args.charts.is_empty() {
+        chart_files()?
+    } else {
+        args.charts.clone()
+    };
+
+    let mut failed = 0;
+    for path in paths.iter() {
+        match migrate_chart_file(path) {
+            Ok(Some(backup)) => println!("{}: upgraded to format version {CURRENT_FORMAT_VERSION}, the original is at {}", path.display(), backup.display()),
+            Ok(None) => println!("{}: already in format version {CURRENT_FORMAT_VERSION}", path.display()),
+            Err(e) => {
+                eprintln!("{}: error: {e:#}", path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} charts could not be upgraded", paths.len());
+    }
+    Ok(())
+}
+
+/// Upgrades one chart file in place, returning where the original was backed up to, if it needed upgrading
+fn migrate_chart_file(path: &Path) -> Result<Option<PathBuf>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading from file {}", path.display()))?;
+    let value: Value = serde_json::from_str(text.as_str())
+        .context("parsing json")?;
+    let version = format_version(&value);
+    if version == CURRENT_FORMAT_VERSION {
+        return Ok(None);
+    }
+
+    // loading upgrades the chart, and makes sure the result is one the game can play
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let chart = Chart::from_json(&ChartName::new(name), text.as_str())?;
+
+    let backup = path.with_extension(format!("json.v{version}.bak"));
+    if backup.exists() {
+        anyhow::bail!("there is already a backup at {}, move it out of the way first", backup.display());
+    }
+    std::fs::copy(path, &backup)
+        .with_context(|| format!("backing up chart to {}", backup.display()))?;
+
+    chart.write_to(path)?;
+    Ok(Some(backup))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().expect("a json object")
+    }
+
+    #[test]
+    fn v0_to_v1_turns_lead_time_secs_into_beats() {
+        let mut chart = object(json!({
+            "beat_duration_secs": 0.5,
+            "lead_time_secs": 2.0,
+        }));
+        v0_to_v1(&mut chart).unwrap();
+        assert_eq!(chart.get("lead_time_beats"), Some(&json!(4.0)));
+        assert!(!chart.contains_key("lead_time_secs"));
+    }
+
+    #[test]
+    fn v0_to_v1_keeps_lead_time_beats_over_lead_time_secs() {
+        let mut chart = object(json!({
+            "beat_duration_secs": 0.5,
+            "lead_time_secs": 2.0,
+            "lead_time_beats": 8.0,
+        }));
+        v0_to_v1(&mut chart).unwrap();
+        assert_eq!(chart.get("lead_time_beats"), Some(&json!(8.0)));
+        assert!(!chart.contains_key("lead_time_secs"));
+    }
+
+    #[test]
+    fn v0_to_v1_needs_a_beat_duration_for_lead_time_secs() {
+        let mut chart = object(json!({
+            "beat_duration_secs": 0.0,
+            "lead_time_secs": 2.0,
+        }));
+        assert!(v0_to_v1(&mut chart).is_err());
+    }
+
+    #[test]
+    fn v0_to_v1_drops_wait_for_music_end() {
+        let mut chart = object(json!({
+            "beat_duration_secs": 0.5,
+            "lead_time_beats": 4.0,
+            "wait_for_music_end": true,
+        }));
+        v0_to_v1(&mut chart).unwrap();
+        assert!(!chart.contains_key("wait_for_music_end"));
+        assert_eq!(chart.get("lead_time_beats"), Some(&json!(4.0)));
+    }
+
+    #[test]
+    fn migrate_sets_the_current_format_version() {
+        let chart = migrate(json!({
+            "beat_duration_secs": 0.5,
+            "lead_time_secs": 2.0,
+        })).unwrap();
+        assert_eq!(format_version(&chart), CURRENT_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn migrate_rejects_charts_from_a_newer_version() {
+        let chart = json!({
+            "format_version": CURRENT_FORMAT_VERSION + 1,
+            "beat_duration_secs": 0.5,
+            "lead_time_beats": 4.0,
+        });
+        let e = migrate(chart).unwrap_err();
+        assert!(e.to_string().contains("newer version of the game"), "unexpected error: {e}");
+    }
+}
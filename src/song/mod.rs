@@ -4,7 +4,8 @@ mod chart;
 pub use chart::{
     Chart,
     ChartName,
-    ChartAssets
+    ChartAssets,
+    Note,
 };
 mod arrow;
 pub use arrow::{
@@ -14,8 +15,26 @@ pub use arrow::{
 mod spawner;
 pub use spawner::{
     ArrowSpawner,
+    SNAP_DRIFT_BEATS,
     SyncSpawnerEvent
 };
+mod seeked_audio;
+pub use seeked_audio::SeekedAudio;
+mod check;
+pub use check::{
+    run_check_charts,
+    CheckArgs,
+};
+mod migrate;
+pub use migrate::{
+    run_migrate_charts,
+    MigrateArgs,
+};
+mod decode;
+pub use decode::{
+    decode_sound_file,
+    DecodedSong,
+};
 
 //
 // Our imports
@@ -23,6 +42,7 @@ pub use spawner::{
 
 use bevy::{
     prelude::*,
+    audio::AddAudioSource,
     sprite::{
         MaterialMesh2dBundle,
         Mesh2dHandle
@@ -34,6 +54,8 @@ use crate::user_settings::UserSettings;
 use crate::team_markers::{
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
     Marker,
 };
 use crate::layout::{
@@ -160,6 +182,16 @@ fn process_load_chart_events<T: Marker>(
         });
 }
 
+/// In a hot-seat match, both users play whichever chart gets picked
+fn follow_local_chart_loads(
+    mut load_chart_ev: EventReader<LoadChartRequest<PlayerMarker>>,
+    mut enemy_load_chart_ev: EventWriter<LoadChartRequest<EnemyMarker>>,
+) {
+    for ev in load_chart_ev.read() {
+        enemy_load_chart_ev.send(LoadChartRequest::from(ev.chart_name().clone()));
+    }
+}
+
 fn process_sync_spawner_events<T: Marker>(
     mut sync_spawner_ev: EventReader<SyncSpawnerEvent<T>>,
     mut commands: Commands,
@@ -182,7 +214,8 @@ fn process_sync_spawner_events<T: Marker>(
                         spawner.load_from_syncable_state(
                             chart_state.clone(),
                             &chart_assets,
-                            settings.latency_tolerance
+                            settings.latency_tolerance,
+                            &time
                         );
                     }
                     None => {
@@ -225,7 +258,10 @@ fn setup_arrows<T: Marker>(
 ) {
     log::info!("in setup_arrows");
 
-    let panel = panel_query.single();
+    let Ok(panel) = panel_query.get_single() else {
+        log::warn!("no {} panel to play the chart on, does everyone have the same number of --players?", T::as_str());
+        return;
+    };
 
     let spawner = spawner_q.single_mut();
     spawner
@@ -334,7 +370,7 @@ fn tick_spawner<T: Marker>(
 }
 
 /// Put the arrows where they need to be
-fn position_arrows<T: Marker>(
+pub fn position_arrows<T: Marker>(
     spawner: Query<&ArrowSpawner<T>>,
     mut arrows: Query<(&mut Transform, &Arrow), With<T>>
 ) {
@@ -410,14 +446,23 @@ impl Plugin for ArrowsPlugin {
             .register_type::<Arrow>()
             .register_type::<Chart>()
             .insert_resource(chart_assets)
+            .add_audio_source::<SeekedAudio>()
 
             // needed for the enemy spawner to keep in sync with remote
             .add_systems(Update, process_sync_spawner_events::<EnemyMarker>)
+            .add_systems(Update, process_sync_spawner_events::<Enemy2Marker>)
+            .add_systems(Update, process_sync_spawner_events::<Enemy3Marker>)
+            // needed for a spectator's player spawner to follow the host
+            .add_systems(Update, process_sync_spawner_events::<PlayerMarker>.run_if(not(crate::remote::not_spectating)))
+            // a hot-seat enemy has nobody to tell it which chart to load
+            .add_systems(Update, follow_local_chart_loads.run_if(crate::remote::playing_locally))
         ;
 
         self
             .build_for_team(app, PlayerMarker{})
             .build_for_team(app, EnemyMarker{})
+            .build_for_team(app, Enemy2Marker{})
+            .build_for_team(app, Enemy3Marker{})
         ;
     }
 }
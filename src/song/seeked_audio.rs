@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+use bevy::audio::{
+    Decodable,
+    Source,
+};
+use bevy::utils::Duration;
+
+/// A song that starts partway through, e.g. when playing a chart from the middle
+#[derive(Asset, TypePath)]
+#[derive(Debug, Clone)]
+pub struct SeekedAudio {
+    source: AudioSource,
+    /// How much of the start of the song to skip
+    skip: Duration,
+}
+impl SeekedAudio {
+    pub fn new(source: AudioSource, skip: Duration) -> SeekedAudio {
+        SeekedAudio {
+            source,
+            skip,
+        }
+    }
+}
+impl Decodable for SeekedAudio {
+    type DecoderItem = <AudioSource as Decodable>::DecoderItem;
+    type Decoder = Box<dyn Source<Item = Self::DecoderItem> + Send>;
+
+    fn decoder(&self) -> Self::Decoder {
+        Box::new(self.source.decoder().skip_duration(self.skip))
+    }
+}
@@ -8,6 +8,18 @@ use serde::{
 
 use crate::team_markers::Marker;
 
+/// Beyond this many beats behind or ahead of the remote, catching up smoothly would take too long, so we jump
+pub const SNAP_DRIFT_BEATS: f32 = 8.0;
+
+/// How much faster the clock runs for each beat we are behind the remote (or slower, when ahead)
+const CATCH_UP_GAIN: f32 = 0.5;
+
+/// The clock never runs more than this fraction faster or slower than real time while catching up
+const MAX_CLOCK_ADJUST: f32 = 0.5;
+
+/// Once we are this close to the remote, we stop correcting
+const CONVERGED_BEATS: f32 = 0.01;
+
 use crate::song::{
     arrow::Arrow,
     chart::{
@@ -33,9 +45,16 @@ pub struct ArrowSpawner<T: Marker> {
     /// The local timestamp when the song started
     song_start: f32,
 
+    /// Arrows arriving before this beat are not spawned, for songs started partway through
+    first_beat: f32,
+
     /// True if we are paused and not making new notes
     is_paused: bool,
 
+    /// Where the remote user last told us they were, while we are catching up to them
+    #[reflect(ignore)]
+    sync_target: Option<SyncTarget>,
+
     /// The team we are spawning for.
     _team: T,
 }
@@ -54,11 +73,22 @@ impl <T: Marker> ArrowSpawner<T> {
             spawn_timer,
             song_start: now,
             scroll_pos: 0.0,
+            first_beat: 0.0,
             is_paused: false,
+            sync_target: None,
             _team: T::marker(),
         }
     }
 
+    /// Creates an arrow spawner partway through the chart.
+    /// The arrows on `beat` come in at the top, and the ones before it are left out.
+    pub fn create_from_beat(chart: Arc<Chart>, time: &Time, beat: f32) -> Self {
+        let mut spawner = Self::create(chart, time);
+        spawner.scroll_pos = beat;
+        spawner.first_beat = beat;
+        spawner
+    }
+
     pub fn change_scroll_pos(&mut self, dy: f32) {
         self.scroll_pos += dy;
     }
@@ -75,14 +105,32 @@ impl <T: Marker> ArrowSpawner<T> {
             return
         }
 
-        self.spawn_timer.tick(time.delta());
+        // run the clock a little faster or slower to catch up with the remote
+        let rate = self.clock_rate(now);
+        self.spawn_timer.tick(time.delta().mul_f32(rate));
 
         if !self.spawn_timer.just_finished() {
             // not time for another beat just yet
             return
         }
 
-        self.change_scroll_pos(1.0);
+        self.change_scroll_pos(self.spawn_timer.times_finished_this_tick() as f32);
+    }
+
+    /// How fast the clock runs compared to real time. Only differs from 1 while catching up to the remote.
+    fn clock_rate(&mut self, now: f32) -> f32 {
+        let Some(target) = self.sync_target.as_ref() else {
+            return 1.0;
+        };
+
+        let error = target.extrapolate(now, self.chart().beat_duration_secs()) - self.scroll_pos();
+        if error.abs() < CONVERGED_BEATS {
+            log::debug!("caught up with remote");
+            self.sync_target = None;
+            return 1.0;
+        }
+
+        1.0 + (error * CATCH_UP_GAIN).clamp(-MAX_CLOCK_ADJUST, MAX_CLOCK_ADJUST)
     }
 
     pub fn song_start(&self) -> f32 {
@@ -136,6 +184,7 @@ impl <T: Marker> ArrowSpawner<T> {
                     arrives
                 )
             })
+            .filter(|arrow| arrow.arrival_beat() >= self.first_beat)
 
     }
 
@@ -161,11 +210,15 @@ impl <T: Marker> ArrowSpawner<T> {
     pub fn from_syncable_state(ev: SpawnerSyncableState, chart_assets: &ChartAssets, latency_tolerance: f32, time: &Time) -> ArrowSpawner<T> {
         let empty = chart_assets.empty();
         let mut spawner = ArrowSpawner::create(empty, time);
-        spawner.load_from_syncable_state(ev, chart_assets, latency_tolerance);
+        spawner.load_from_syncable_state(ev, chart_assets, latency_tolerance, time);
         spawner
     }
-    pub fn load_from_syncable_state(&mut self, ev: SpawnerSyncableState, chart_assets: &ChartAssets, latency_tolerance: f32) {
+    /// Follows the remote user's spawner.
+    /// Small drifts in `scroll_pos` are corrected by running our clock faster or slower until we converge,
+    /// so that the arrows do not visibly jump. Only very large drifts are corrected all at once.
+    pub fn load_from_syncable_state(&mut self, ev: SpawnerSyncableState, chart_assets: &ChartAssets, latency_tolerance: f32, time: &Time) {
         let SpawnerSyncableState { chart_name, scroll_pos, is_paused, } = ev;
+        let now = time.elapsed().as_secs_f32();
 
         chart_name
             // Only change it on a new chart
@@ -177,19 +230,56 @@ impl <T: Marker> ArrowSpawner<T> {
             .then(|chart| {
                 log::warn!("changing charts, this could cause arrows to desync");
                 self.chart = chart;
+                // a target on the old chart means nothing on the new one
+                self.sync_target = None;
             });
 
         scroll_pos
-            // Only change if the jump is big enough
-            .filter(|scroll_pos| (scroll_pos - self.scroll_pos).abs() >= latency_tolerance)
+            // Only change if the jump is big enough, or if we are already catching up
+            .filter(|scroll_pos| {
+                self.sync_target.is_some() || (scroll_pos - self.scroll_pos()).abs() >= latency_tolerance
+            })
             .then(|scroll_pos| {
-                self.scroll_pos = scroll_pos; 
+                if (scroll_pos - self.scroll_pos()).abs() >= SNAP_DRIFT_BEATS {
+                    log::info!("too far from remote to catch up smoothly, jumping from {:.2} to {scroll_pos:.2}", self.scroll_pos());
+                    self.scroll_pos = scroll_pos;
+                    self.sync_target = None;
+                } else {
+                    self.sync_target = Some(SyncTarget {
+                        scroll_pos,
+                        received_at: now,
+                    });
+                }
             });
 
         is_paused
             .then(|is_paused| {
                 self.is_paused = is_paused;
             });
+
+        // a paused clock can not catch up, and nobody is watching the arrows move anyway
+        if self.is_paused {
+            if let Some(target) = self.sync_target.take() {
+                self.scroll_pos = target.scroll_pos;
+            }
+        }
+    }
+}
+
+/// The remote user's scroll position, as of the last sync
+#[derive(Debug, Clone)]
+struct SyncTarget {
+    scroll_pos: f32,
+    /// The local timestamp when we heard about it
+    received_at: f32,
+}
+impl SyncTarget {
+    /// Where the remote user should be by now, assuming they kept scrolling since the last sync
+    fn extrapolate(&self, now: f32, beat_duration_secs: f32) -> f32 {
+        if beat_duration_secs <= 0.0 {
+            return self.scroll_pos;
+        }
+        self.scroll_pos + (now - self.received_at) / beat_duration_secs
     }
 }
 
@@ -206,6 +296,16 @@ pub enum SyncSpawnerEvent<T: Marker> {
     NotSpawning,
     Spawning(SpawnerSyncableState, T)
 }
+impl <T: Marker> SyncSpawnerEvent<T> {
+    /// The same state, but to be applied to a different team's spawner
+    pub fn for_team<U: Marker>(self) -> SyncSpawnerEvent<U> {
+        use SyncSpawnerEvent::*;
+        match self {
+            NotSpawning => NotSpawning,
+            Spawning(state, _team) => Spawning(state, U::marker()),
+        }
+    }
+}
 
 trait OptionExt<T> : Sized {
     fn to_option(self) -> Option<T>;
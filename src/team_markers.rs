@@ -20,6 +20,21 @@ pub struct PlayerMarker;
 /// Put this component on entities owned by the remote user
 pub struct EnemyMarker;
 
+#[derive(Component)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash,Deserialize,Serialize)]
+#[derive(Reflect)]
+/// Put this component on entities owned by the second remote user, in a match of three or more
+pub struct Enemy2Marker;
+
+#[derive(Component)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash,Deserialize,Serialize)]
+#[derive(Reflect)]
+/// Put this component on entities owned by the third remote user, in a match of four
+pub struct Enemy3Marker;
+
+/// Most users that can play in one match, counting the local user
+pub const MAX_PLAYERS: usize = 4;
+
 #[derive(Debug,Copy,Clone,PartialEq,Eq,Hash,Deserialize,Serialize)]
 #[derive(Reflect)]
 /// Runtime available information on the object's side.
@@ -27,7 +42,20 @@ pub enum Team {
     /// Local user.
     Player,
     /// Remote user.
-    Enemy
+    Enemy,
+    /// Second remote user.
+    Enemy2,
+    /// Third remote user.
+    Enemy3,
+}
+impl Team {
+    /// Every seat in a match, in order. The local user always sits first.
+    pub const SEATS: [Team; MAX_PLAYERS] = [Team::Player, Team::Enemy, Team::Enemy2, Team::Enemy3];
+
+    /// The team sitting in this seat, if there are that many seats
+    pub fn from_seat(seat: usize) -> Option<Team> {
+        Team::SEATS.get(seat).copied()
+    }
 }
 
 pub trait Marker : Component 
@@ -48,13 +76,15 @@ pub trait Marker : Component
     }
     fn is_remote() -> bool {
         match Self::team() {
-            Team::Enemy => true,
+            Team::Enemy | Team::Enemy2 | Team::Enemy3 => true,
             Team::Player => false,
         }
     }
     fn as_str() -> &'static str {
         match Self::team() {
             Team::Enemy => "enemy",
+            Team::Enemy2 => "enemy2",
+            Team::Enemy3 => "enemy3",
             Team::Player => "player",
         }
     }
@@ -81,3 +111,19 @@ impl Marker for EnemyMarker {
         Team::Enemy
     }
 }
+impl Marker for Enemy2Marker {
+    fn marker() -> Enemy2Marker {
+        Enemy2Marker{}
+    }
+    fn team() -> Team {
+        Team::Enemy2
+    }
+}
+impl Marker for Enemy3Marker {
+    fn marker() -> Enemy3Marker {
+        Enemy3Marker{}
+    }
+    fn team() -> Team {
+        Team::Enemy3
+    }
+}
@@ -17,6 +17,7 @@ use crate::{
     CliArgs,
     project_dirs,
 };
+use crate::team_markers::Team;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[derive(Resource)]
@@ -30,18 +31,54 @@ pub struct UserSettings {
     pub host_addr: IpAddr,
     #[serde(default = "default_window_mode")]
     pub window_mode: WindowMode,
+    /// How many beats the remote user's arrows may drift before we start catching up to them
     #[serde(default = "default_latency_tolerance")]
-    pub latency_tolerance: f32
+    pub latency_tolerance: f32,
+    /// The name other users see when looking for hosts on the local network
+    #[serde(default = "default_player_name")]
+    pub player_name: String,
+    /// Whether to announce ourselves on the local network while listening
+    #[serde(default = "default_announce_on_lan")]
+    pub announce_on_lan: bool,
+    /// The UDP port that announcements are broadcast to
+    #[serde(default = "default_discovery_port")]
+    pub discovery_port: u16,
+    /// Whether to listen with `wss://`, and connect with it when the address does not say
+    #[serde(default = "default_use_tls")]
+    pub use_tls: bool,
+    /// Both users need the same code to play directly, and spectators need it to watch. When unset, anyone may connect.
+    #[serde(default)]
+    pub pairing_code: Option<String>,
+    /// Fake latency, jitter, loss and reordering on the connection, for testing
+    #[serde(default)]
+    pub net_sim: crate::remote::netsim::NetSimSettings,
+    /// The click on every beat while recording, editing or practising
+    #[serde(default)]
+    pub metronome: crate::metronome::MetronomeSettings,
 }
 
-/// Default latency in milli seconds
+/// Default latency tolerance in beats. Catching up is smooth, so this can be small
 fn default_latency_tolerance() -> f32 {
-    1000.0
+    0.25
 }
 
 fn default_port() -> u16 {
     8080
 }
+fn default_player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or("anonymous".to_owned())
+}
+fn default_announce_on_lan() -> bool {
+    true
+}
+fn default_discovery_port() -> u16 {
+    8081
+}
+fn default_use_tls() -> bool {
+    false
+}
 fn default_host_addr() -> IpAddr {
     IpAddr::from([0,0,0,0])
 }
@@ -72,18 +109,46 @@ impl std::default::Default for UserSettings {
             host_addr: default_host_addr(),
             port: default_port(),
             keybindings: KeyBindings::default(),
+            player_name: default_player_name(),
+            announce_on_lan: default_announce_on_lan(),
+            discovery_port: default_discovery_port(),
+            use_tls: default_use_tls(),
+            pairing_code: None,
+            net_sim: Default::default(),
+            metronome: Default::default(),
         }
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct KeyBindings {
     #[serde(default)]
     pub lane_hit_keymap: crate::input::LaneHitControls,
+    /// Keys for the enemy panel in a hot-seat match, i.e. `local` mode
+    #[serde(default = "crate::input::LaneHitControls::second_player")]
+    pub second_lane_hit_keymap: crate::input::LaneHitControls,
     #[serde(default)]
     pub recording_keymap: crate::record::controls::RecordingKeymap,
 }
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            lane_hit_keymap: Default::default(),
+            second_lane_hit_keymap: crate::input::LaneHitControls::second_player(),
+            recording_keymap: Default::default(),
+        }
+    }
+}
+impl KeyBindings {
+    /// The keys that hit lanes on team's panel. Only the local user and, in a hot-seat match, the enemy are played from this keyboard.
+    pub fn lane_hit_keymap_for(&self, team: Team) -> &crate::input::LaneHitControls {
+        match team {
+            Team::Player => &self.lane_hit_keymap,
+            _ => &self.second_lane_hit_keymap,
+        }
+    }
+}
 
 const SETTINGS_FILENAME: &str = "settings.toml";
 fn settings_path(cli: &CliArgs) -> PathBuf {
@@ -115,8 +180,20 @@ pub fn load_settings(cli: &CliArgs) -> Result<UserSettings> {
         let contents = fs::read_to_string(&path)
             .with_context(|| format!("reading settings file at {display_path}"))?;
 
-        let settings = toml::from_str(contents.as_ref())
+        let mut settings: UserSettings = toml::from_str(contents.as_ref())
             .with_context(|| format!("deserializing settings.toml file at {display_path}"))?;
+        settings.net_sim = settings.net_sim.validated();
+
+        // older settings files gave this in seconds, with a default of 1000. Any drift that large is
+        // jumped over rather than caught up with, so a value past it can only be in the old units
+        if !(0.0..crate::song::SNAP_DRIFT_BEATS).contains(&settings.latency_tolerance) {
+            log::warn!(
+                "latency_tolerance of {} looks like it is from an older version of the game, which measured it in seconds. Using the default of {} beats",
+                settings.latency_tolerance,
+                default_latency_tolerance(),
+            );
+            settings.latency_tolerance = default_latency_tolerance();
+        }
 
         settings
     };
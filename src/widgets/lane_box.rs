@@ -20,6 +20,8 @@ use crate::input::{
 use crate::team_markers::{
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
     Marker,
 };
 use crate::lane::{
@@ -125,7 +127,10 @@ fn create_lane_box_on_press<T: Marker>(
     // listen for the triggers
     mut lane_hit_ev: EventReader<RawLaneHit<T>>,
 ) {
-    let panel = panel.single();
+    let Ok(panel) = panel.get_single() else {
+        lane_hit_ev.clear();
+        return; // nobody is sitting in this seat
+    };
 
     for ev in lane_hit_ev.read() {
         let lane = ev.lane();
@@ -171,6 +176,8 @@ impl Plugin for LaneBoxPlugin {
             .add_plugins(Material2dPlugin::<LaneBoxMaterial>::default())
             .add_systems(Update, create_lane_box_on_press::<PlayerMarker>)
             .add_systems(Update, create_lane_box_on_press::<EnemyMarker>)
+            .add_systems(Update, create_lane_box_on_press::<Enemy2Marker>)
+            .add_systems(Update, create_lane_box_on_press::<Enemy3Marker>)
             .add_systems(Update, animate_lane_boxes)
         ;
     }
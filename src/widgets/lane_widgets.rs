@@ -52,7 +52,9 @@ pub fn setup_lane_targets<T: Marker>(
     mut commands: Commands,
     panel_q: Query<&SongPanel, With<T>>,
 ) {
-    let panel = panel_q.single();
+    let Ok(panel) = panel_q.get_single() else {
+        return; // nobody is sitting in this seat
+    };
 
     for (lane, bounds) in panel.lanes().iter() {
         let lane_target = LaneTarget {
@@ -106,7 +108,7 @@ pub fn setup_lane_letters<T: Marker>(
 
     for (lane, bounds) in panel.lanes().iter() {
 
-        let key = settings.keybindings.lane_hit_keymap.keycode(lane);
+        let key = settings.keybindings.lane_hit_keymap_for(T::team()).keycode(lane);
         let text_content = crate::keycode_serde::to_name(key)
             .inspect_err(|e| {
                 log::error!("could not setup lane letter for lane {lane:?}, {e}")
@@ -8,10 +8,14 @@ mod sound_alerts;
 use crate::team_markers::{
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
 };
 use crate::layout::{
     LayoutState
 };
+use crate::remote::playing_locally;
+use crate::coop::in_coop;
 
 pub struct WidgetsPlugin;
 impl Plugin for WidgetsPlugin {
@@ -27,9 +31,13 @@ impl Plugin for WidgetsPlugin {
             // everything that needs the song panels to set up gets run here
             .add_systems(OnEnter(LayoutState::Done), (
                     lane_widgets::setup_lane_targets::<PlayerMarker>,
-                    lane_widgets::setup_lane_targets::<EnemyMarker>,
+                    // a co-op partner plays on our panel, so the enemy panel is only left with their status and emotes
+                    lane_widgets::setup_lane_targets::<EnemyMarker>.run_if(not(in_coop)),
+                    lane_widgets::setup_lane_targets::<Enemy2Marker>,
+                    lane_widgets::setup_lane_targets::<Enemy3Marker>,
 
                     lane_widgets::setup_lane_letters::<PlayerMarker>,
+                    lane_widgets::setup_lane_letters::<EnemyMarker>.run_if(playing_locally),
             ))
             .add_systems(Update, lane_widgets::darken_on_press)
             .add_systems(Update, lane_widgets::darken_over_time)
@@ -17,6 +17,8 @@ use bevy::{
 use crate::team_markers::{
     PlayerMarker,
     EnemyMarker,
+    Enemy2Marker,
+    Enemy3Marker,
     Marker,
 };
 use crate::lane::{
@@ -64,7 +66,10 @@ fn create_target_sparkle_on_correct_hit<T: Marker>(
     mut correct_events: EventReader<RawCorrectHitEvent<T>>,
 ) {
     let now = time.elapsed().as_secs_f32();
-    let panel = panel.single();
+    let Ok(panel) = panel.get_single() else {
+        correct_events.clear();
+        return; // nobody is sitting in this seat
+    };
 
     let initial_radius = 10.0;
 
@@ -116,7 +121,9 @@ fn update_target_sparkles<T: Marker>(
     panel: Query<&SongPanel, With<T>>,
 ) {
     let now = time.elapsed().as_secs_f32();
-    let panel = panel.single();
+    let Ok(panel) = panel.get_single() else {
+        return; // nobody is sitting in this seat
+    };
 
     // they should all be the same, but the ideal would be to get the appropriate lane
     let initial_radius = panel.lane_bounds(Lane::L1).width() * TARGET_SPARKLE_INITIAL_RADIUS;
@@ -154,6 +161,12 @@ impl Plugin for TargetSparklesPlugin {
 
             .add_systems(Update, create_target_sparkle_on_correct_hit::<EnemyMarker>)
             .add_systems(Update, update_target_sparkles::<EnemyMarker>)
+
+            .add_systems(Update, create_target_sparkle_on_correct_hit::<Enemy2Marker>)
+            .add_systems(Update, update_target_sparkles::<Enemy2Marker>)
+
+            .add_systems(Update, create_target_sparkle_on_correct_hit::<Enemy3Marker>)
+            .add_systems(Update, update_target_sparkles::<Enemy3Marker>)
         ;
 
     }
@@ -56,7 +56,7 @@ def main(beat_count: int, path: Path):
 if __name__ == '__main__':
     parser = argparse.ArgumentParser(
         prog='make_random_chart.py',
-        description='Randomly generated charts for saffron-rhythm-duel game',
+        description='Randomly generated charts for saffron-rhythm-duel game. For a draft chart that follows a song, use the generate command of the game instead.',
     )
     parser.add_argument('-n', '--number',
         dest='beat_count',